@@ -38,6 +38,11 @@ use modules::{
     import::cmd_import,
     list::cmd_list,
     post,
+    principal::cmd_principal,
+    report::cmd_report,
+    template::cmd_template,
+    timezone::cmd_timezones,
+    whois::cmd_whois,
 };
 
 use crate::modules::OAuthResponse;
@@ -46,7 +51,24 @@ pub mod modules;
 
 fn main() {
     let args = Cli::parse();
-    let credentials = if let Some(credentials) = args.credentials {
+    let client = || connect(&args.url, args.credentials);
+
+    match args.command {
+        Commands::Account(command) => cmd_account(client(), command),
+        Commands::Domain(command) => cmd_domain(client(), command, &args.url),
+        Commands::List(command) => cmd_list(client(), command),
+        Commands::Group(command) => cmd_group(client(), command),
+        Commands::Import(command) => cmd_import(client(), command),
+        Commands::Report(command) => cmd_report(client(), command),
+        Commands::Principal(command) => cmd_principal(client(), command),
+        Commands::Whois { address } => cmd_whois(client(), address),
+        Commands::Timezones(command) => cmd_timezones(command),
+        Commands::Template(command) => cmd_template(command),
+    }
+}
+
+fn connect(url: &str, credentials: Option<String>) -> Client {
+    let credentials = if let Some(credentials) = credentials {
         parse_credentials(&credentials)
     } else {
         let credentials =
@@ -55,25 +77,17 @@ fn main() {
         if !credentials.is_empty() {
             parse_credentials(&credentials)
         } else {
-            oauth(&args.url)
+            oauth(url)
         }
     };
 
-    let client = Client::new()
+    Client::new()
         .credentials(credentials)
-        .connect(&args.url)
+        .connect(url)
         .unwrap_or_else(|err| {
-            eprintln!("Failed to connect to JMAP server {}: {}.", args.url, err);
+            eprintln!("Failed to connect to JMAP server {}: {}.", url, err);
             std::process::exit(1);
-        });
-
-    match args.command {
-        Commands::Account(command) => cmd_account(client, command),
-        Commands::Domain(command) => cmd_domain(client, command),
-        Commands::List(command) => cmd_list(client, command),
-        Commands::Group(command) => cmd_group(client, command),
-        Commands::Import(command) => cmd_import(client, command),
-    }
+        })
 }
 
 fn parse_credentials(credentials: &str) -> Credentials {
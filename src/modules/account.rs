@@ -21,20 +21,32 @@
  * for more details.
 */
 
+use std::io::Write;
+
 use jmap_client::{
     client::Client,
-    core::{query::Filter, set::SetObject},
+    core::set::SetObject,
     principal::{query, Property, Type},
 };
 
-use crate::modules::{common::email_to_id, UnwrapResult};
+use crate::modules::{common::email_to_id, confirm, UnwrapResult};
 
 use super::{
-    cli::AccountCommands,
-    common::{display_principal, list_principals},
+    address::{available_aliases, taken_addresses, validate_aliases},
+    cli::{AccountCommands, MailboxFormat, OutputFormat},
+    common::{
+        add_to_groups, bulk_update, display_principal, fetch_principals, list_principals,
+        principal_quota, remove_from_memberships,
+    },
+    config::Config,
+    domain::{domain_exists, domain_timezone},
+    export::{export_account, transfer_account},
+    picture::Picture,
+    timezone::validate_timezone,
+    usage::{principals_usage, print_usage, USAGE_PROPERTIES},
 };
 
-pub fn cmd_account(client: Client, command: AccountCommands) {
+pub fn cmd_account(mut client: Client, command: AccountCommands) {
     match command {
         AccountCommands::Create {
             email,
@@ -44,31 +56,46 @@ pub fn cmd_account(client: Client, command: AccountCommands) {
             quota,
             timezone,
             email_aliases,
+            allow_foreign_domain,
+            template,
         } => {
-            // Create the domain if missing
-            if let Some((_, domain)) = email.rsplit_once('@') {
-                let domain_ = domain.to_ascii_lowercase();
-                if client
-                    .principal_query(
-                        Filter::and([
-                            query::Filter::ptype(Type::Domain),
-                            query::Filter::domain_name(domain_),
-                        ])
-                        .into(),
-                        None::<Vec<_>>,
-                    )
-                    .unwrap_result("query principals")
-                    .ids()
-                    .is_empty()
-                {
-                    // Create domain
-                    client
-                        .domain_create(domain)
-                        .unwrap_result(&format!("Failed to create domain '{}'", domain));
-                }
+            let domain = if let Some((_, domain)) = email.rsplit_once('@') {
+                domain
             } else {
                 eprintln!("Invalid email address '{}'", email);
                 std::process::exit(1);
+            };
+
+            // Apply template and domain defaults for any field not provided
+            let config = Config::load();
+            let defaults = config.account_defaults(template.as_deref(), domain);
+            let description = description.or(defaults.description);
+            let quota = quota
+                .or(defaults.quota)
+                .and_then(|quota| principal_quota(quota).unwrap_result("set quota"));
+            let timezone = validate_timezone(timezone)
+                .or(defaults.timezone)
+                .or_else(|| domain_timezone(&client, &domain.to_ascii_lowercase()));
+            let mut email_aliases = email_aliases
+                .map(|email_aliases| {
+                    validate_aliases(&client, None, &email, &email_aliases, allow_foreign_domain)
+                })
+                .unwrap_or_default();
+            let alias_domain_addresses = config.alias_domain_addresses(&email);
+            if !alias_domain_addresses.is_empty() {
+                let taken = taken_addresses(&client);
+                for alias in available_aliases(alias_domain_addresses, &taken) {
+                    if !email_aliases.contains(&alias) {
+                        email_aliases.push(alias);
+                    }
+                }
+            }
+
+            // Create the domain if missing, once all the input has been validated
+            if !domain_exists(&client, &domain.to_ascii_lowercase()) {
+                client
+                    .domain_create(domain)
+                    .unwrap_result(&format!("Failed to create domain '{}'", domain));
             }
 
             let mut request = client.build();
@@ -87,15 +114,25 @@ pub fn cmd_account(client: Client, command: AccountCommands) {
             if timezone.is_some() {
                 create_request.timezone(timezone);
             }
-            if email_aliases.is_some() {
-                create_request.aliases(email_aliases);
+            if !email_aliases.is_empty() {
+                create_request.aliases(Some(email_aliases));
             }
             let create_id = create_request.create_id().unwrap();
-            request
+            let account_id = request
                 .send_set_principal()
                 .unwrap_result("create account")
                 .created(&create_id)
-                .unwrap_result("create account");
+                .unwrap_result("create account")
+                .take_id();
+            if !defaults.groups.is_empty() {
+                add_to_groups(
+                    &client,
+                    defaults
+                        .groups
+                        .iter()
+                        .map(|group| (account_id.as_str(), group.as_str())),
+                );
+            }
             eprintln!("Account '{}' successfully created.", email);
         }
         AccountCommands::Update {
@@ -106,6 +143,7 @@ pub fn cmd_account(client: Client, command: AccountCommands) {
             quota,
             timezone,
         } => {
+            let timezone = validate_timezone(timezone);
             let update_id = email_to_id(&client, Type::Individual, &email);
             let mut request = client.build();
             let update_request = request.set_principal().update(&update_id);
@@ -119,11 +157,7 @@ pub fn cmd_account(client: Client, command: AccountCommands) {
                 update_request.description(description);
             }
             if let Some(quota) = quota {
-                if quota > 0 {
-                    update_request.quota(quota.into());
-                } else {
-                    update_request.quota(None);
-                }
+                update_request.quota(principal_quota(quota).unwrap_result("set quota"));
             }
             if timezone.is_some() {
                 update_request.timezone(timezone);
@@ -135,9 +169,64 @@ pub fn cmd_account(client: Client, command: AccountCommands) {
                 .unwrap_result("update account");
             eprintln!("Account '{}' successfully updated.", email);
         }
-        AccountCommands::Delete { email } => {
+        AccountCommands::Delete {
+            email,
+            force,
+            export_to,
+            export_format,
+            transfer_to,
+        } => {
+            let account_id = email_to_id(&client, Type::Individual, &email);
+            let target_account_id = transfer_to.as_ref().map(|transfer_to| {
+                let target_account_id = email_to_id(&client, Type::Individual, transfer_to);
+                if target_account_id == account_id {
+                    eprintln!("Error: Cannot transfer messages to the account being deleted.");
+                    std::process::exit(1);
+                }
+                target_account_id
+            });
+
+            if !force
+                && !confirm(&format!(
+                    "Account '{}' and all its messages will be permanently deleted. Continue?",
+                    email
+                ))
+            {
+                eprintln!("Operation cancelled.");
+                return;
+            }
+
+            if let Some(export_to) = export_to {
+                eprintln!("Exporting messages to '{}'...", export_to.display());
+                export_account(
+                    &mut client,
+                    &account_id,
+                    &export_to,
+                    export_format.unwrap_or(MailboxFormat::Maildir),
+                );
+            }
+
+            if let (Some(transfer_to), Some(target_account_id)) = (transfer_to, target_account_id) {
+                eprintln!("Transferring messages to '{}'...", transfer_to);
+                let (total_copied, failures) =
+                    transfer_account(&mut client, &account_id, &target_account_id, &email);
+                eprintln!(
+                    "\nCopied {} messages to folder '{}' of account '{}'.",
+                    total_copied, email, transfer_to
+                );
+                if !failures.is_empty() {
+                    eprintln!("There were {} failures:\n", failures.len());
+                    for failure in &failures {
+                        eprintln!("{}", failure);
+                    }
+                    eprintln!("\nAccount '{}' was not deleted.", email);
+                    std::process::exit(1);
+                }
+            }
+
+            remove_from_memberships(&client, &account_id);
             client
-                .principal_destroy(&email_to_id(&client, Type::Individual, &email))
+                .principal_destroy(&account_id)
                 .unwrap_result("delete account");
             eprintln!("Account '{}' successfully deleted.", email);
         }
@@ -165,11 +254,46 @@ pub fn cmd_account(client: Client, command: AccountCommands) {
                     Property::Quota,
                     Property::Timezone,
                     Property::Aliases,
+                    Property::Capabilities,
+                    Property::ACL,
                 ],
             );
         }
-        AccountCommands::AddAlias { email, aliases } => {
+        AccountCommands::Usage { email, format } => {
+            let principals = if let Some(email) = email {
+                vec![client
+                    .principal_get(
+                        &email_to_id(&client, Type::Individual, &email),
+                        USAGE_PROPERTIES.iter().cloned().into(),
+                    )
+                    .unwrap_result("fetch principal")
+                    .unwrap_result("find account")]
+            } else {
+                fetch_principals(
+                    &client,
+                    query::Filter::ptype(Type::Individual).into(),
+                    &USAGE_PROPERTIES,
+                )
+            };
+
+            print_usage(
+                &principals_usage(&client, &principals),
+                format.unwrap_or(OutputFormat::Table),
+            );
+        }
+        AccountCommands::AddAlias {
+            email,
+            aliases,
+            allow_foreign_domain,
+        } => {
             let update_id = email_to_id(&client, Type::Individual, &email);
+            let aliases = validate_aliases(
+                &client,
+                Some(&update_id),
+                &email,
+                &aliases,
+                allow_foreign_domain,
+            );
             let mut request = client.build();
             let update_request = request.set_principal().update(&update_id);
 
@@ -200,5 +324,73 @@ pub fn cmd_account(client: Client, command: AccountCommands) {
                 .unwrap_result("update account");
             eprintln!("Account '{}' successfully updated.", email);
         }
+        AccountCommands::SetPicture { email, path } => {
+            let picture = Picture::from_file(&path).unwrap_result("load picture");
+            let update_id = email_to_id(&client, Type::Individual, &email);
+            let mut request = client.build();
+            request
+                .set_principal()
+                .update(&update_id)
+                .picture(picture.to_data_uri().into());
+            request
+                .send_set_principal()
+                .unwrap_result("update account")
+                .updated(&update_id)
+                .unwrap_result("update account");
+            eprintln!(
+                "Account '{}' successfully updated with a {}x{} {} picture.",
+                email, picture.width, picture.height, picture.mime_type
+            );
+        }
+        AccountCommands::GetPicture { email, output } => {
+            let picture = client
+                .principal_get(
+                    &email_to_id(&client, Type::Individual, &email),
+                    [Property::Picture].into(),
+                )
+                .unwrap_result("fetch principal")
+                .unwrap_result("find account")
+                .picture()
+                .map(|picture| picture.to_string())
+                .unwrap_or_else(|| {
+                    eprintln!("Account '{}' has no picture.", email);
+                    std::process::exit(1);
+                });
+            let contents = if picture.starts_with("data:") {
+                Picture::from_data_uri(&picture).unwrap_result("decode picture")
+            } else {
+                client.download(&picture).unwrap_result("download picture")
+            };
+
+            if let Some(output) = output {
+                std::fs::write(&output, contents).unwrap_result("write picture");
+                eprintln!("Picture saved to '{}'.", output.display());
+            } else {
+                std::io::stdout()
+                    .write_all(&contents)
+                    .unwrap_result("write picture");
+            }
+        }
+        AccountCommands::BulkUpdate {
+            filter,
+            force,
+            description,
+            quota,
+            timezone,
+        } => {
+            let timezone = validate_timezone(timezone);
+            let quota = quota.map(|quota| principal_quota(quota).unwrap_result("set quota"));
+            bulk_update(&client, Type::Individual, filter, force, |update_request| {
+                if description.is_some() {
+                    update_request.description(description.clone());
+                }
+                if let Some(quota) = quota {
+                    update_request.quota(quota);
+                }
+                if timezone.is_some() {
+                    update_request.timezone(timezone.clone());
+                }
+            });
+        }
     }
 }
@@ -0,0 +1,329 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Command Line Interface.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use jmap_client::{client::Client, principal::Property};
+
+use super::{
+    common::{fetch_addressable_principals, has_alias},
+    domain::domain_exists,
+};
+
+pub fn normalize_address(address: &str) -> Result<String, String> {
+    let address = address.trim();
+    let (local_part, domain) = address
+        .rsplit_once('@')
+        .ok_or_else(|| format!("'{}' is not an e-mail address", address))?;
+
+    if local_part.is_empty() {
+        return Err(format!("'{}' has an empty local part", address));
+    } else if local_part.len() > 64 {
+        return Err(format!(
+            "'{}' has a local part longer than 64 octets",
+            address
+        ));
+    } else if !is_valid_local_part(local_part) {
+        return Err(format!("'{}' has an invalid local part", address));
+    }
+
+    let address = format!(
+        "{}@{}",
+        local_part.to_lowercase(),
+        normalize_domain(domain).map_err(|err| format!("'{}' {}", address, err))?
+    );
+    if address.len() > 254 {
+        Err(format!("'{}' is longer than 254 octets", address))
+    } else {
+        Ok(address)
+    }
+}
+
+pub fn normalize_domain(domain: &str) -> Result<String, String> {
+    let domain = idna::domain_to_ascii(domain.trim().trim_end_matches('.'))
+        .map_err(|_| format!("has an invalid internationalized domain name '{}'", domain))?;
+
+    if domain.is_empty() || domain.len() > 253 {
+        return Err(format!("has an invalid domain name length '{}'", domain));
+    }
+    for label in domain.split('.') {
+        if label.is_empty()
+            || label.len() > 63
+            || label.starts_with('-')
+            || label.ends_with('-')
+            || !label
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '-')
+        {
+            return Err(format!("has an invalid domain name '{}'", domain));
+        }
+    }
+
+    Ok(domain)
+}
+
+/// Converts the punycode labels of a domain name to Unicode for display.
+pub fn display_domain(domain: &str) -> Cow<'_, str> {
+    if domain.contains("xn--") {
+        if let (domain, Ok(_)) = idna::domain_to_unicode(domain) {
+            return domain.into();
+        }
+    }
+    domain.into()
+}
+
+pub fn display_address(address: &str) -> Cow<'_, str> {
+    match address.rsplit_once('@') {
+        Some((local_part, domain)) if domain.contains("xn--") => {
+            format!("{}@{}", local_part, display_domain(domain)).into()
+        }
+        _ => address.into(),
+    }
+}
+
+pub fn address_domain(address: &str) -> Option<&str> {
+    address.rsplit_once('@').map(|(_, domain)| domain)
+}
+
+fn is_valid_local_part(local_part: &str) -> bool {
+    if let Some(quoted) = local_part
+        .strip_prefix('"')
+        .and_then(|local_part| local_part.strip_suffix('"'))
+    {
+        // Quoted string
+        let mut is_escaped = false;
+        for ch in quoted.chars() {
+            if is_escaped {
+                is_escaped = false;
+            } else if ch == '\\' {
+                is_escaped = true;
+            } else if ch == '"' || (ch.is_ascii() && !(' '..='~').contains(&ch)) {
+                return false;
+            }
+        }
+        !is_escaped
+    } else {
+        // Dot-atom, non-ASCII characters are allowed as per RFC 6531
+        local_part.split('.').all(|atom| {
+            !atom.is_empty()
+                && atom.chars().all(|ch| {
+                    ch.is_ascii_alphanumeric()
+                        || !ch.is_ascii()
+                        || "!#$%&'*+-/=?^_`{|}~".contains(ch)
+                })
+        })
+    }
+}
+
+pub fn validate_aliases(
+    client: &Client,
+    owner_id: Option<&str>,
+    owner_email: &str,
+    aliases: &[String],
+    allow_foreign_domain: bool,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut results: Vec<String> = Vec::with_capacity(aliases.len());
+    for alias in aliases {
+        match normalize_address(alias) {
+            Ok(alias) => {
+                if !results.contains(&alias) {
+                    results.push(alias);
+                }
+            }
+            Err(err) => errors.push(format!("Invalid alias {}.", err)),
+        }
+    }
+
+    // Make sure aliases belong to a hosted domain, the owner's domain is
+    // created along with a new account if missing
+    if !allow_foreign_domain {
+        let mut hosted_domains = HashMap::new();
+        if let Some(owner_domain) = address_domain(owner_email) {
+            hosted_domains.insert(owner_domain, true);
+        }
+        for alias in &results {
+            let domain = address_domain(alias).unwrap();
+            if !*hosted_domains
+                .entry(domain)
+                .or_insert_with(|| domain_exists(client, domain))
+            {
+                errors.push(format!(
+                    concat!(
+                        "Alias '{}' does not belong to a domain hosted on this server, ",
+                        "use --allow-foreign-domain to override."
+                    ),
+                    alias
+                ));
+            }
+        }
+    }
+
+    // Make sure aliases are not used by any other principal
+    let principals =
+        fetch_addressable_principals(client, &[Property::Id, Property::Email, Property::Aliases]);
+    for alias in &results {
+        if alias.eq_ignore_ascii_case(owner_email) {
+            errors.push(format!(
+                "Alias '{}' is the primary e-mail address of '{}'.",
+                alias, owner_email
+            ));
+            continue;
+        }
+
+        for principal in &principals {
+            if owner_id.is_some() && principal.id() == owner_id {
+                continue;
+            }
+            let email = principal.email().unwrap_or("");
+            if email.eq_ignore_ascii_case(alias) {
+                errors.push(format!(
+                    "Alias '{}' is already the primary e-mail address of '{}'.",
+                    alias, email
+                ));
+            } else if has_alias(principal, alias) {
+                errors.push(format!(
+                    "Alias '{}' is already in use by '{}'.",
+                    alias, email
+                ));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        for error in errors {
+            eprintln!("Error: {}", error);
+        }
+        std::process::exit(1);
+    }
+
+    results
+}
+
+/// Returns the primary addresses and aliases of all accounts, groups and lists.
+pub fn taken_addresses(client: &Client) -> HashSet<String> {
+    fetch_addressable_principals(client, &[Property::Email, Property::Aliases])
+        .into_iter()
+        .flat_map(|principal| {
+            principal
+                .email()
+                .map(|email| email.to_string())
+                .into_iter()
+                .chain(principal.aliases().into_iter().flatten().cloned())
+                .collect::<Vec<_>>()
+        })
+        .map(|address| address.to_ascii_lowercase())
+        .collect()
+}
+
+/// Removes the alias domain addresses of a new account that are already in
+/// use, warning about each one of them.
+pub fn available_aliases(aliases: Vec<String>, taken: &HashSet<String>) -> Vec<String> {
+    aliases
+        .into_iter()
+        .filter(|alias| {
+            if taken.contains(&alias.to_ascii_lowercase()) {
+                eprintln!(
+                    "Warning: Address '{}' is already in use, not adding it as an alias.",
+                    alias
+                );
+                false
+            } else {
+                true
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_address, normalize_domain};
+
+    #[test]
+    fn normalize_addresses() {
+        for (address, expected) in [
+            ("John.Doe@Example.COM", "john.doe@example.com"),
+            ("  user+tag@example.com.  ", "user+tag@example.com"),
+            ("user@Exämple.com", "user@xn--exmple-cua.com"),
+            ("\"john doe\"@example.com", "\"john doe\"@example.com"),
+            ("\"john@doe\"@example.com", "\"john@doe\"@example.com"),
+            ("\"john\\\"doe\"@example.com", "\"john\\\"doe\"@example.com"),
+        ] {
+            assert_eq!(normalize_address(address).as_deref(), Ok(expected));
+        }
+    }
+
+    #[test]
+    fn reject_invalid_addresses() {
+        for address in [
+            "example.com",
+            "@example.com",
+            "user@",
+            "john..doe@example.com",
+            ".john@example.com",
+            "john doe@example.com",
+            "\"john\"doe\"@example.com",
+            "\"john\\\"@example.com",
+            "\"john\ndoe\"@example.com",
+            "user@-example.com",
+            "user@exa_mple.com",
+            "user@example..com",
+        ] {
+            assert!(normalize_address(address).is_err(), "{}", address);
+        }
+        assert!(normalize_address(&format!("{}@example.com", "a".repeat(64))).is_ok());
+        assert!(normalize_address(&format!("{}@example.com", "a".repeat(65))).is_err());
+    }
+
+    #[test]
+    fn normalize_domains() {
+        assert_eq!(
+            normalize_domain("Example.COM.").as_deref(),
+            Ok("example.com")
+        );
+        assert_eq!(
+            normalize_domain("münchen.de").as_deref(),
+            Ok("xn--mnchen-3ya.de")
+        );
+        assert_eq!(
+            normalize_domain("xn--mnchen-3ya.de").as_deref(),
+            Ok("xn--mnchen-3ya.de")
+        );
+        assert!(normalize_domain(&format!("{}.com", "a".repeat(63))).is_ok());
+        for domain in [
+            "",
+            ".",
+            "example..com",
+            "-example.com",
+            "example-.com",
+            "exa mple.com",
+            &format!("{}.com", "a".repeat(64)),
+            &format!("{}com", "a.".repeat(126)),
+        ] {
+            assert!(normalize_domain(domain).is_err(), "{}", domain);
+        }
+    }
+}
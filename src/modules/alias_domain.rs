@@ -0,0 +1,219 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Command Line Interface.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_client::{
+    client::Client,
+    principal::{Principal, Property, Type},
+};
+use prettytable::{Attr, Cell, Row, Table};
+
+use super::{
+    address::{display_domain, taken_addresses},
+    cli::AliasDomainCommands,
+    common::{has_alias, principals_in_domain, update_principals},
+    config::Config,
+    domain::{domain_exists, domain_to_id},
+    UnwrapResult,
+};
+
+pub fn cmd_alias_domain(client: Client, command: AliasDomainCommands) {
+    match command {
+        AliasDomainCommands::Add {
+            alias_domain,
+            target_domain,
+        } => {
+            let alias_domain = alias_domain.to_ascii_lowercase();
+            let target_domain = target_domain.to_ascii_lowercase();
+            if alias_domain == target_domain {
+                eprintln!("Error: A domain cannot be an alias of itself.");
+                std::process::exit(1);
+            }
+            let mut config = Config::load();
+            if let Some(target) = config.alias_domains.get(&alias_domain) {
+                eprintln!(
+                    "Error: Domain '{}' is already an alias of '{}'.",
+                    alias_domain, target
+                );
+                std::process::exit(1);
+            }
+            if config.alias_domains.contains_key(&target_domain) {
+                eprintln!(
+                    "Error: Domain '{}' is itself an alias domain.",
+                    target_domain
+                );
+                std::process::exit(1);
+            }
+            domain_to_id(&client, &target_domain);
+            if !domain_exists(&client, &alias_domain) {
+                client
+                    .domain_create(&alias_domain)
+                    .unwrap_result(&format!("create domain '{}'", alias_domain));
+                eprintln!("Domain '{}' successfully created.", alias_domain);
+            }
+
+            config
+                .alias_domains
+                .insert(alias_domain.clone(), target_domain.clone());
+            config.save();
+
+            sync_alias_domain(&client, &alias_domain, &target_domain);
+            eprintln!(
+                "Messages for '{}' will be delivered to the accounts of '{}'.",
+                alias_domain, target_domain
+            );
+        }
+        AliasDomainCommands::Remove { alias_domain } => {
+            let alias_domain = alias_domain.to_ascii_lowercase();
+            let mut config = Config::load();
+            let target_domain = config
+                .alias_domains
+                .remove(&alias_domain)
+                .unwrap_or_else(|| {
+                    eprintln!("Error: Domain '{}' is not an alias domain.", alias_domain);
+                    std::process::exit(1);
+                });
+
+            let accounts = principals_in_domain(
+                &client,
+                Type::Individual,
+                &target_domain,
+                &[Property::Id, Property::Email, Property::Aliases],
+            )
+            .into_iter()
+            .filter(|account| has_alias(account, &alias_address(account, &alias_domain)))
+            .collect::<Vec<_>>();
+            if !accounts.is_empty() {
+                update_principals(&client, &accounts, |account, update_request| {
+                    update_request.alias(&alias_address(account, &alias_domain), false);
+                });
+            }
+            config.save();
+            eprintln!(
+                "Domain '{}' is no longer an alias of '{}'.",
+                alias_domain, target_domain
+            );
+        }
+        AliasDomainCommands::Sync { alias_domain } => {
+            let config = Config::load();
+            let alias_domains = if let Some(alias_domain) = alias_domain {
+                let alias_domain = alias_domain.to_ascii_lowercase();
+                let target_domain = config
+                    .alias_domains
+                    .get(&alias_domain)
+                    .cloned()
+                    .unwrap_or_else(|| {
+                        eprintln!("Error: Domain '{}' is not an alias domain.", alias_domain);
+                        std::process::exit(1);
+                    });
+                vec![(alias_domain, target_domain)]
+            } else {
+                config.alias_domains.into_iter().collect()
+            };
+
+            for (alias_domain, target_domain) in alias_domains {
+                let total = sync_alias_domain(&client, &alias_domain, &target_domain);
+                eprintln!(
+                    "Added {} alias{} in domain '{}'.",
+                    total,
+                    if total == 1 { "" } else { "es" },
+                    alias_domain
+                );
+            }
+        }
+        AliasDomainCommands::List => {
+            let config = Config::load();
+            if !config.alias_domains.is_empty() {
+                let mut table = Table::new();
+                table.add_row(Row::new(
+                    ["Alias domain", "Target domain"]
+                        .iter()
+                        .map(|h| Cell::new(h).with_style(Attr::Bold))
+                        .collect(),
+                ));
+                for (alias_domain, target_domain) in &config.alias_domains {
+                    table.add_row(Row::new(vec![
+                        Cell::new(&display_domain(alias_domain)),
+                        Cell::new(&display_domain(target_domain)),
+                    ]));
+                }
+                eprintln!();
+                table.printstd();
+            }
+            eprintln!(
+                "\n\n{} record{} found.\n",
+                config.alias_domains.len(),
+                if config.alias_domains.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                }
+            );
+        }
+    }
+}
+
+/// Adds the alias domain address to every account of the target domain that
+/// is missing it, skipping addresses already owned by another principal.
+fn sync_alias_domain(client: &Client, alias_domain: &str, target_domain: &str) -> usize {
+    let taken = taken_addresses(client);
+    let accounts = principals_in_domain(
+        client,
+        Type::Individual,
+        target_domain,
+        &[Property::Id, Property::Email, Property::Aliases],
+    )
+    .into_iter()
+    .filter(|account| {
+        let alias = alias_address(account, alias_domain);
+        if has_alias(account, &alias) {
+            false
+        } else if taken.contains(&alias.to_ascii_lowercase()) {
+            eprintln!(
+                "Warning: Address '{}' is already in use, skipping account '{}'.",
+                alias,
+                account.email().unwrap_or("")
+            );
+            false
+        } else {
+            true
+        }
+    })
+    .collect::<Vec<_>>();
+    if !accounts.is_empty() {
+        update_principals(client, &accounts, |account, update_request| {
+            update_request.alias(&alias_address(account, alias_domain), true);
+        });
+    }
+    accounts.len()
+}
+
+fn alias_address(account: &Principal, alias_domain: &str) -> String {
+    let email = account.email().unwrap_or("");
+    format!(
+        "{}@{}",
+        email
+            .rsplit_once('@')
+            .map_or(email, |(local_part, _)| local_part),
+        alias_domain
+    )
+}
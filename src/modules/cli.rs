@@ -21,9 +21,14 @@
  * for more details.
 */
 
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
+use super::{
+    address::{normalize_address, normalize_domain},
+    usage::parse_percentage,
+};
+
 #[derive(Parser)]
 #[clap(version, about, long_about = None)]
 #[clap(name = "stalwart-cli")]
@@ -59,6 +64,28 @@ pub enum Commands {
     /// Import accounts and domains
     #[clap(subcommand)]
     Import(ImportCommands),
+
+    /// Generate reports
+    #[clap(subcommand)]
+    Report(ReportCommands),
+
+    /// Manage principal permissions
+    #[clap(subcommand)]
+    Principal(PrincipalCommands),
+
+    /// Find the principals receiving messages for an address
+    Whois {
+        /// E-mail address to look up
+        address: String,
+    },
+
+    /// List valid timezones
+    #[clap(subcommand)]
+    Timezones(TimezoneCommands),
+
+    /// Manage account templates
+    #[clap(subcommand)]
+    Template(TemplateCommands),
 }
 
 #[derive(Subcommand)]
@@ -66,6 +93,7 @@ pub enum AccountCommands {
     /// Create a new user account
     Create {
         /// Login email address
+        #[clap(value_parser = normalize_address)]
         email: String,
         /// Password
         password: String,
@@ -74,15 +102,21 @@ pub enum AccountCommands {
         /// Account description
         #[clap(short, long)]
         description: Option<String>,
-        /// Quota in bytes
-        #[clap(short, long)]
-        quota: Option<u32>,
-        /// Timezone
+        /// Quota in bytes or with a unit suffix (e.g. '500MB'), below 4GB
+        #[clap(short, long, value_parser = parse_quota)]
+        quota: Option<u64>,
+        /// Timezone, defaults to the domain's timezone
         #[clap(short, long)]
         timezone: Option<String>,
         /// E-mail address aliases
         #[clap(short, long)]
         email_aliases: Option<Vec<String>>,
+        /// Allow aliases on domains not hosted by this server
+        #[clap(long)]
+        allow_foreign_domain: bool,
+        /// Account template to use for any field not provided
+        #[clap(long)]
+        template: Option<String>,
     },
 
     /// Update an existing user account
@@ -98,9 +132,9 @@ pub enum AccountCommands {
         /// Update account description
         #[clap(short, long)]
         description: Option<String>,
-        /// Update quota in bytes
-        #[clap(short, long)]
-        quota: Option<u32>,
+        /// Update quota in bytes or with a unit suffix (e.g. '500MB'), below 4GB
+        #[clap(short, long, value_parser = parse_quota)]
+        quota: Option<u64>,
         /// Update timezone
         #[clap(short, long)]
         timezone: Option<String>,
@@ -113,6 +147,9 @@ pub enum AccountCommands {
         /// Aliases to add
         #[clap(required = true)]
         aliases: Vec<String>,
+        /// Allow aliases on domains not hosted by this server
+        #[clap(long)]
+        allow_foreign_domain: bool,
     },
 
     /// Add e-mail aliases to a user account
@@ -120,7 +157,7 @@ pub enum AccountCommands {
         /// Account email address
         email: String,
         /// Aliases to remove
-        #[clap(required = true)]
+        #[clap(required = true, value_parser = normalize_address)]
         aliases: Vec<String>,
     },
 
@@ -128,6 +165,19 @@ pub enum AccountCommands {
     Delete {
         /// Account name to delete
         email: String,
+        /// Do not ask for confirmation
+        #[clap(short, long)]
+        force: bool,
+        /// Export all messages to this directory before deleting the account
+        #[clap(short, long)]
+        export_to: Option<PathBuf>,
+        /// Export format, defaults to 'maildir'
+        #[clap(arg_enum)]
+        #[clap(long)]
+        export_format: Option<MailboxFormat>,
+        /// Copy all mailboxes to this account before deleting the account
+        #[clap(short, long)]
+        transfer_to: Option<String>,
     },
 
     /// Display an existing user account
@@ -138,6 +188,52 @@ pub enum AccountCommands {
 
     /// List all user accounts
     List { filter: Option<String> },
+
+    /// Set the picture of a user account
+    SetPicture {
+        /// Account email address
+        email: String,
+        /// Path to a PNG, JPEG, GIF or WebP image
+        path: PathBuf,
+    },
+
+    /// Save the picture of a user account to a file
+    GetPicture {
+        /// Account email address
+        email: String,
+        /// Output file, defaults to stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Update all user accounts matching a filter
+    BulkUpdate {
+        /// Only update accounts matching this filter, defaults to all accounts
+        #[clap(long)]
+        filter: Option<String>,
+        /// Do not ask for confirmation
+        #[clap(short, long)]
+        force: bool,
+        /// Update account description
+        #[clap(short, long)]
+        description: Option<String>,
+        /// Update quota in bytes or with a unit suffix (e.g. '500MB'), below 4GB
+        #[clap(short, long, value_parser = parse_quota)]
+        quota: Option<u64>,
+        /// Update timezone
+        #[clap(short, long)]
+        timezone: Option<String>,
+    },
+
+    /// Display storage usage for one or all user accounts
+    Usage {
+        /// Account email address, defaults to all accounts
+        email: Option<String>,
+        /// Output format, defaults to 'table'
+        #[clap(arg_enum)]
+        #[clap(short, long)]
+        format: Option<OutputFormat>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -145,6 +241,7 @@ pub enum DomainCommands {
     /// Create a new domain
     Create {
         /// Domain name to create
+        #[clap(value_parser = parse_domain)]
         name: String,
         /// Description
         #[clap(short, long)]
@@ -152,17 +249,32 @@ pub enum DomainCommands {
         /// Path to DKIM private key.
         #[clap(short, long)]
         cert_dkim: Option<PathBuf>,
+        /// Generate a new DKIM key pair, the algorithm defaults to 'rsa-2048'
+        #[clap(arg_enum)]
+        #[clap(
+            short,
+            long,
+            conflicts_with = "cert-dkim",
+            min_values = 0,
+            max_values = 1,
+            default_missing_value = "rsa-2048"
+        )]
+        generate_dkim: Option<DkimAlgorithm>,
         /// DKIM selector
         #[clap(short, long)]
         selector_dkim: Option<String>,
         /// DKIM expiration (in seconds)
         #[clap(short, long)]
         expiration_dkim: Option<u64>,
+        /// Default timezone for new accounts
+        #[clap(short, long)]
+        timezone: Option<String>,
     },
 
     /// Update an existing domain
     Update {
         /// Domain name to update
+        #[clap(value_parser = parse_domain)]
         name: String,
         /// Description
         #[clap(short, long)]
@@ -170,28 +282,209 @@ pub enum DomainCommands {
         /// Path to DKIM private key.
         #[clap(short, long)]
         cert_dkim: Option<PathBuf>,
+        /// Generate a new DKIM key pair, the algorithm defaults to 'rsa-2048'
+        #[clap(arg_enum)]
+        #[clap(
+            short,
+            long,
+            conflicts_with = "cert-dkim",
+            min_values = 0,
+            max_values = 1,
+            default_missing_value = "rsa-2048"
+        )]
+        generate_dkim: Option<DkimAlgorithm>,
         /// DKIM selector
         #[clap(short, long)]
         selector_dkim: Option<String>,
         /// DKIM expiration (in seconds)
         #[clap(short, long)]
         expiration_dkim: Option<u64>,
+        /// Default timezone for new accounts
+        #[clap(short, long)]
+        timezone: Option<String>,
     },
 
     /// Delete an existing domain
     Delete {
         /// Domain name to delete
+        #[clap(value_parser = parse_domain)]
         name: String,
+        /// Delete principals in the domain and detach their aliases
+        #[clap(long)]
+        cascade: bool,
+        /// Move principals and aliases in the domain to another domain
+        #[clap(long, conflicts_with = "cascade", value_parser = parse_domain)]
+        reassign_to: Option<String>,
+        /// Do not ask for confirmation
+        #[clap(short, long)]
+        force: bool,
+    },
+
+    /// Rename a domain, moving all addresses in it to the new domain
+    Rename {
+        /// Domain name to rename
+        #[clap(value_parser = parse_domain)]
+        name: String,
+        /// New domain name
+        #[clap(value_parser = parse_domain)]
+        new_name: String,
+        /// Keep the old addresses as aliases
+        #[clap(short, long)]
+        keep_aliases: bool,
+        /// Do not ask for confirmation
+        #[clap(short, long)]
+        force: bool,
     },
 
     /// Display an existing domain
     Display {
         /// Domain name to display
+        #[clap(value_parser = parse_domain)]
         name: String,
     },
 
     /// List all domains
-    List { filter: Option<String> },
+    List {
+        filter: Option<String>,
+        /// Include the number of principals, aliases and quota in each domain
+        #[clap(short, long)]
+        stats: bool,
+    },
+
+    /// Manage the catch-all address of a domain
+    #[clap(subcommand)]
+    CatchAll(CatchAllCommands),
+
+    /// Manage domains that mirror the addresses of another domain
+    #[clap(subcommand)]
+    Alias(AliasDomainCommands),
+
+    /// Manage default settings for new accounts in a domain
+    #[clap(subcommand)]
+    Defaults(DomainDefaultsCommands),
+
+    /// Display storage usage for all user accounts in a domain
+    Usage {
+        /// Domain name
+        #[clap(value_parser = parse_domain)]
+        name: String,
+        /// Output format, defaults to 'table'
+        #[clap(arg_enum)]
+        #[clap(short, long)]
+        format: Option<OutputFormat>,
+    },
+
+    /// Check that the DNS records of a domain are published correctly
+    Verify {
+        /// Domain name
+        #[clap(value_parser = parse_domain)]
+        name: String,
+        /// DNS resolver address (e.g. '127.0.0.1:53'), defaults to the system resolver
+        #[clap(short, long)]
+        resolver: Option<String>,
+    },
+
+    /// Rotate the DKIM key of a domain
+    #[clap(subcommand)]
+    Dkim(DkimCommands),
+
+    /// Display the DNS records required by a domain
+    Dns {
+        /// Domain name
+        #[clap(value_parser = parse_domain)]
+        name: String,
+        /// Output format, defaults to 'bind'
+        #[clap(arg_enum)]
+        #[clap(short, long)]
+        format: Option<DnsFormat>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DomainDefaultsCommands {
+    /// Set default settings for new accounts in a domain
+    Set {
+        /// Domain name
+        #[clap(value_parser = parse_domain)]
+        name: String,
+        #[clap(flatten)]
+        defaults: AccountDefaultsArgs,
+    },
+
+    /// Remove all default settings of a domain
+    Clear {
+        /// Domain name
+        #[clap(value_parser = parse_domain)]
+        name: String,
+    },
+
+    /// Display the default settings of a domain
+    Display {
+        /// Domain name
+        #[clap(value_parser = parse_domain)]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TemplateCommands {
+    /// Create or update an account template
+    Set {
+        /// Template name
+        name: String,
+        #[clap(flatten)]
+        defaults: AccountDefaultsArgs,
+    },
+
+    /// Delete an account template
+    Delete {
+        /// Template name
+        name: String,
+    },
+
+    /// Display an account template
+    Display {
+        /// Template name
+        name: String,
+    },
+
+    /// List all account templates
+    List,
+}
+
+#[derive(Args)]
+pub struct AccountDefaultsArgs {
+    /// Account description
+    #[clap(short, long)]
+    pub description: Option<String>,
+    /// Quota in bytes or with a unit suffix (e.g. '500MB'), below 4GB
+    #[clap(short, long, value_parser = parse_quota)]
+    pub quota: Option<u64>,
+    /// Timezone
+    #[clap(short, long)]
+    pub timezone: Option<String>,
+    /// Groups new accounts are added to
+    #[clap(short, long)]
+    pub groups: Vec<String>,
+}
+
+#[derive(Subcommand)]
+pub enum CatchAllCommands {
+    /// Deliver messages for unknown addresses in a domain to a principal
+    Set {
+        /// Domain name
+        #[clap(value_parser = parse_domain)]
+        name: String,
+        /// E-mail address of the account, group or list receiving the messages
+        email: String,
+    },
+
+    /// Remove the catch-all address of a domain
+    Clear {
+        /// Domain name
+        #[clap(value_parser = parse_domain)]
+        name: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -199,6 +492,7 @@ pub enum ListCommands {
     /// Create a new mailing list
     Create {
         /// List email address
+        #[clap(value_parser = normalize_address)]
         email: String,
         /// Name
         name: String,
@@ -223,9 +517,12 @@ pub enum ListCommands {
     AddMembers {
         /// List email address
         email: String,
-        /// Members to add
+        /// Members to add, addresses in other domains require --external
         #[clap(required = true)]
         members: Vec<String>,
+        /// Create external recipients for addresses in domains not hosted by this server
+        #[clap(long)]
+        external: bool,
     },
 
     /// Remove members from a mailing list
@@ -237,13 +534,41 @@ pub enum ListCommands {
         members: Vec<String>,
     },
 
+    /// Delete an existing mailing list
+    Delete {
+        /// List email address to delete
+        email: String,
+        /// Do not ask for confirmation
+        #[clap(short, long)]
+        force: bool,
+        /// List the members that will lose their membership
+        #[clap(short, long)]
+        show_members: bool,
+    },
+
     /// List all mailing lists
     List { filter: Option<String> },
 
+    /// Update all mailing lists matching a filter
+    BulkUpdate {
+        /// Only update mailing lists matching this filter, defaults to all lists
+        #[clap(long)]
+        filter: Option<String>,
+        /// Do not ask for confirmation
+        #[clap(short, long)]
+        force: bool,
+        /// Description
+        #[clap(short, long)]
+        description: Option<String>,
+    },
+
     /// Display an existing mailing list
     Display {
         /// Mailing list to display
         email: String,
+        /// Show all accounts receiving messages instead of the member tree
+        #[clap(short, long)]
+        expand: bool,
     },
 }
 
@@ -252,6 +577,7 @@ pub enum GroupCommands {
     /// Create a group
     Create {
         /// Group email address
+        #[clap(value_parser = normalize_address)]
         email: String,
         /// Name
         name: String,
@@ -290,14 +616,42 @@ pub enum GroupCommands {
         members: Vec<String>,
     },
 
+    /// Delete an existing group
+    Delete {
+        /// Group email address to delete
+        email: String,
+        /// Do not ask for confirmation
+        #[clap(short, long)]
+        force: bool,
+        /// List the members that will lose their membership
+        #[clap(short, long)]
+        show_members: bool,
+    },
+
     /// Display an existing group
     Display {
         /// Group email address to display
         email: String,
+        /// Show all accounts receiving messages instead of the member tree
+        #[clap(short, long)]
+        expand: bool,
     },
 
     /// List all groups
     List { filter: Option<String> },
+
+    /// Update all groups matching a filter
+    BulkUpdate {
+        /// Only update groups matching this filter, defaults to all groups
+        #[clap(long)]
+        filter: Option<String>,
+        /// Do not ask for confirmation
+        #[clap(short, long)]
+        force: bool,
+        /// Description
+        #[clap(short, long)]
+        description: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -312,7 +666,8 @@ pub enum ImportCommands {
         #[clap(short, long)]
         with_headers: bool,
 
-        /// CSV column layout, default is 'email,secret,name,description,quota,timezone'
+        /// CSV column layout, default is 'email,secret,name,description,quota,timezone'.
+        /// Also accepts a 'picture' column with the path to the account picture.
         #[clap(short, long)]
         column_layout: Option<String>,
 
@@ -320,6 +675,10 @@ pub enum ImportCommands {
         #[clap(short, long)]
         no_domains: bool,
 
+        /// Account template to use for any field not provided
+        #[clap(short, long)]
+        template: Option<String>,
+
         /// Path to the CSV file, or '-' for stdin
         path: String,
     },
@@ -342,6 +701,155 @@ pub enum ImportCommands {
     },
 }
 
+#[derive(Subcommand)]
+pub enum PrincipalCommands {
+    /// Manage access control lists
+    #[clap(subcommand)]
+    Acl(AclCommands),
+
+    /// Manage capabilities
+    #[clap(subcommand)]
+    Capabilities(CapabilityCommands),
+}
+
+#[derive(Subcommand)]
+pub enum AclCommands {
+    /// Display the access control list of a principal
+    Show {
+        /// Principal email address or domain name
+        principal: String,
+    },
+
+    /// Grant rights on a principal
+    Grant {
+        /// Principal email address or domain name
+        principal: String,
+        /// Email address or domain name of the principal to grant rights to
+        grantee: String,
+        /// Rights to grant (e.g. 'read', 'modify', 'administer')
+        #[clap(required = true)]
+        rights: Vec<String>,
+    },
+
+    /// Revoke rights on a principal
+    Revoke {
+        /// Principal email address or domain name
+        principal: String,
+        /// Email address or domain name of the principal to revoke rights from
+        grantee: String,
+        /// Rights to revoke, defaults to all rights
+        rights: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CapabilityCommands {
+    /// Display the capabilities of a principal
+    Show {
+        /// Principal email address or domain name
+        principal: String,
+    },
+
+    /// Replace the capabilities of a principal
+    Set {
+        /// Principal email address or domain name
+        principal: String,
+        /// Capabilities to set, leave empty to remove all capabilities
+        capabilities: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TimezoneCommands {
+    /// List all timezones in the IANA database
+    List { filter: Option<String> },
+}
+
+#[derive(Subcommand)]
+pub enum AliasDomainCommands {
+    /// Make a domain receive messages for all accounts of another domain
+    ///
+    /// The mapping is kept in the local CLI configuration only. Accounts created
+    /// from another machine or tool do not receive the alias, run
+    /// 'domain alias sync' to add it to them.
+    Add {
+        /// Alias domain name, created if missing
+        #[clap(value_parser = parse_domain)]
+        alias_domain: String,
+        /// Domain whose accounts receive the messages
+        #[clap(value_parser = parse_domain)]
+        target_domain: String,
+    },
+
+    /// Remove an alias domain and the aliases it added
+    Remove {
+        /// Alias domain name
+        #[clap(value_parser = parse_domain)]
+        alias_domain: String,
+    },
+
+    /// Add the alias domain addresses to all accounts that are missing them
+    Sync {
+        /// Alias domain name, defaults to all alias domains
+        #[clap(value_parser = parse_domain)]
+        alias_domain: Option<String>,
+    },
+
+    /// List all alias domains
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum DkimCommands {
+    /// Generate a new DKIM key under a new selector, pending activation
+    Rotate {
+        /// Domain name
+        #[clap(value_parser = parse_domain)]
+        name: String,
+        /// Key algorithm, defaults to 'rsa-2048'
+        #[clap(arg_enum)]
+        #[clap(short, long)]
+        algorithm: Option<DkimAlgorithm>,
+        /// DKIM selector, defaults to the current date
+        #[clap(short, long)]
+        selector: Option<String>,
+        /// File to write the new private key to, readable only by its owner
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+
+    /// Start signing with the pending DKIM key of a domain
+    Activate {
+        /// Domain name
+        #[clap(value_parser = parse_domain)]
+        name: String,
+        /// DKIM expiration (in seconds), defaults to the current expiration
+        #[clap(short, long)]
+        expiration_dkim: Option<u64>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ReportCommands {
+    /// List user accounts that are close to their quota
+    Quota {
+        /// Usage threshold, defaults to '90%'
+        #[clap(short, long, value_parser = parse_percentage)]
+        over: Option<f64>,
+        /// Output format, defaults to 'table'
+        #[clap(arg_enum)]
+        #[clap(short, long)]
+        format: Option<OutputFormat>,
+    },
+
+    /// List domains whose DKIM keys are due for rotation
+    Dkim {
+        /// Maximum key age in days when the domain has no DKIM expiration, defaults to 180
+        #[clap(short, long)]
+        max_age: Option<u64>,
+    },
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum MailboxFormat {
     /// Mbox format
@@ -351,3 +859,121 @@ pub enum MailboxFormat {
     /// Maildir with hierarchical folders (i.e. Dovecot)
     MaildirNested,
 }
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum DnsFormat {
+    /// BIND zone file
+    Bind,
+    /// JSON
+    Json,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum DkimAlgorithm {
+    /// RSA with a 2048-bit key
+    #[clap(name = "rsa-2048")]
+    Rsa2048,
+    /// RSA with a 4096-bit key
+    #[clap(name = "rsa-4096")]
+    Rsa4096,
+    /// Ed25519
+    Ed25519,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable table
+    Table,
+    /// Comma separated values
+    Csv,
+    /// JSON
+    Json,
+}
+
+pub fn parse_domain(value: &str) -> Result<String, String> {
+    normalize_domain(value).map_err(|err| format!("the domain {}", err))
+}
+
+pub fn parse_quota(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (number, unit) = value.split_at(
+        value
+            .find(|ch: char| !ch.is_ascii_digit() && ch != '.')
+            .unwrap_or(value.len()),
+    );
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1024,
+        "M" | "MB" | "MIB" => 1024 * 1024,
+        "G" | "GB" | "GIB" => 1024 * 1024 * 1024,
+        "T" | "TB" | "TIB" => 1024 * 1024 * 1024 * 1024,
+        _ => return Err(format!("invalid unit '{}'", unit.trim())),
+    };
+    let quota = if number.contains('.') {
+        let quota = number
+            .parse::<f64>()
+            .map_err(|_| format!("invalid quota '{}'", value))?
+            * multiplier as f64;
+        Some(quota)
+            .filter(|quota| *quota < u64::MAX as f64)
+            .map(|quota| quota as u64)
+    } else if !number.is_empty() {
+        number
+            .parse::<u64>()
+            .ok()
+            .and_then(|quota| quota.checked_mul(multiplier))
+    } else {
+        return Err(format!("invalid quota '{}'", value));
+    };
+
+    quota.ok_or_else(|| {
+        format!(
+            "quota '{}' exceeds the maximum of {} bytes",
+            value,
+            u64::MAX
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_quota;
+
+    #[test]
+    fn parse_quotas() {
+        for (value, expected) in [
+            ("0", 0),
+            ("1024", 1024),
+            ("100 B", 100),
+            ("1k", 1024),
+            ("2 KB", 2 * 1024),
+            ("1.5M", 1024 * 1024 * 3 / 2),
+            ("10GiB", 10 * 1024 * 1024 * 1024),
+            ("8 GB", 8 * 1024 * 1024 * 1024),
+            (" 2tb ", 2 * 1024 * 1024 * 1024 * 1024),
+            ("18446744073709551615", u64::MAX),
+        ] {
+            assert_eq!(parse_quota(value), Ok(expected), "{}", value);
+        }
+    }
+
+    #[test]
+    fn reject_invalid_quotas() {
+        for value in ["", "GB", "-1", "1.2.3M", "10 PB", "ten"] {
+            assert!(parse_quota(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn reject_overflowing_quotas() {
+        for value in [
+            "18446744073709551616",
+            "18014398509481984 KB",
+            "16777216 TB",
+            "16777216.5 TB",
+        ] {
+            assert!(parse_quota(value).is_err(), "{}", value);
+        }
+        assert_eq!(parse_quota("16777215 TB"), Ok(16777215 << 40));
+    }
+}
@@ -21,55 +21,667 @@
  * for more details.
 */
 
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashMap};
 
 use jmap_client::{
     client::Client,
     core::query::Filter,
     principal::{
         query::{self, Comparator},
-        Principal, Property, Type,
+        Principal, Property, Type, ACL,
     },
+    Set,
 };
 use prettytable::{Attr, Cell, Row, Table};
 
-use super::{TableName, UnwrapResult};
+use super::{
+    address::{address_domain, display_address, display_domain, normalize_address},
+    config::Config,
+    confirm,
+    domain::{domain_exists, domain_to_id},
+    TableName, UnwrapResult,
+};
 
 pub fn email_to_id(client: &Client, ptype: Type, email: &str) -> String {
+    resolve_email(client, &[ptype], email)
+}
+
+pub fn resolve_email(client: &Client, ptypes: &[Type], email: &str) -> String {
+    let normalize =
+        |value: &str| normalize_address(value).unwrap_or_else(|_| value.trim().to_lowercase());
+    let address = normalize(email);
+    let type_filter = || {
+        Filter::or(
+            ptypes
+                .iter()
+                .map(|ptype| query::Filter::ptype(ptype.clone())),
+        )
+    };
+
     let mut response = client
         .principal_query(
-            Filter::and([query::Filter::ptype(ptype), query::Filter::email(email)]).into(),
+            Filter::and([type_filter(), query::Filter::email(&address).into()]).into(),
             None::<Vec<_>>,
         )
         .unwrap_result("query principals");
-    match response.ids().len() {
-        1 => response.take_ids().pop().unwrap(),
+    if response.ids().len() == 1 {
+        return response.take_ids().pop().unwrap();
+    }
+
+    // Fall back to matching aliases and addresses stored with a different case or encoding
+    let principals = fetch_principals(
+        client,
+        type_filter(),
+        &[Property::Id, Property::Email, Property::Aliases],
+    );
+    let mut matches = principals
+        .iter()
+        .filter(|principal| {
+            principal
+                .email()
+                .map_or(false, |value| normalize(value) == address)
+                || principal.aliases().map_or(false, |aliases| {
+                    aliases.iter().any(|value| normalize(value) == address)
+                })
+        })
+        .collect::<Vec<_>>();
+
+    match matches.len() {
+        1 => matches
+            .pop()
+            .unwrap()
+            .id()
+            .unwrap_result("obtain principal id")
+            .to_string(),
         0 => {
             eprintln!("Error: No principal found with email '{}'.", email);
+            let mut suggestions = principals
+                .iter()
+                .filter_map(|principal| principal.email())
+                .map(|value| (edit_distance(&normalize(value), &address), value))
+                .filter(|(distance, _)| *distance <= 3)
+                .collect::<Vec<_>>();
+            if !suggestions.is_empty() {
+                suggestions.sort_unstable();
+                eprintln!(
+                    "Did you mean {}?",
+                    suggestions
+                        .iter()
+                        .take(5)
+                        .map(|(_, value)| format!("'{}'", value))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
             std::process::exit(1);
         }
         _ => {
-            eprintln!("Error: Multiple principals found with email '{}'.", email);
+            eprintln!(
+                "Error: Multiple principals found with email '{}': {}.",
+                email,
+                matches
+                    .iter()
+                    .map(|principal| format!("'{}'", principal.email().unwrap_or("")))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
             std::process::exit(1);
         }
     }
 }
 
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ch_a) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, ch_b) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ch_a == *ch_b {
+                prev
+            } else {
+                1 + prev.min(current).min(row[j])
+            };
+            prev = current;
+        }
+    }
+    row[b.len()]
+}
+
 pub fn list_principals(
     client: &Client,
     ptype: Type,
     filter: Option<String>,
     properties: &[Property],
 ) {
-    let filter = if let Some(filter) = filter {
+    let results = fetch_principals(
+        client,
+        text_filter(ptype, filter),
+        &with_type_property(properties),
+    );
+
+    if !results.is_empty() {
+        // Build table
+        let mut table = Table::new();
+        table.add_row(Row::new(
+            properties
+                .iter()
+                .map(|p| Cell::new(p.table_name()).with_style(Attr::Bold))
+                .collect(),
+        ));
+
+        for principal in &results {
+            table.add_row(Row::new(build_cells(client, principal, properties)));
+        }
+
+        eprintln!();
+        table.printstd();
+    }
+
+    eprintln!(
+        "\n\n{} record{} found.\n",
+        results.len(),
+        if results.len() == 1 { "" } else { "s" }
+    );
+}
+
+pub fn principal_to_id(client: &Client, name: &str) -> String {
+    if name.contains('@') {
+        resolve_email(client, &[Type::Individual, Type::Group, Type::List], name)
+    } else {
+        domain_to_id(client, name)
+    }
+}
+
+pub fn principal_names<'x>(
+    client: &Client,
+    ids: impl IntoIterator<Item = &'x String>,
+) -> HashMap<String, String> {
+    let mut request = client.build();
+    request
+        .get_principal()
+        .ids(ids)
+        .properties([Property::Id, Property::Email, Property::Name]);
+    request
+        .send_get_principal()
+        .unwrap_result("fetch principals")
+        .take_list()
+        .into_iter()
+        .filter_map(|principal| {
+            let name = principal
+                .email()
+                .or_else(|| principal.name())
+                .unwrap_or("")
+                .to_string();
+            principal.id().map(|id| (id.to_string(), name))
+        })
+        .collect()
+}
+
+pub fn parse_acl(right: &str) -> ACL {
+    serde_json::from_value(serde_json::Value::String(right.to_string())).unwrap_or_else(|_| {
+        eprintln!("Error: Invalid access right '{}'.", right);
+        std::process::exit(1);
+    })
+}
+
+pub fn acl_name(right: &ACL) -> String {
+    match serde_json::to_value(right) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => format!("{:?}", right),
+    }
+}
+
+pub fn type_name(ptype: &Type) -> &'static str {
+    match ptype {
+        Type::Individual => "individual",
+        Type::Group => "group",
+        Type::Resource => "resource",
+        Type::Location => "location",
+        Type::Domain => "domain",
+        Type::List => "list",
+        Type::Other => "other",
+    }
+}
+
+pub fn expand_members(principals: &HashMap<&str, &Principal>, id: &str) -> Vec<String> {
+    let mut recipients = Vec::new();
+    let mut visited = vec![id];
+    let mut pending = vec![id];
+
+    while let Some(id) = pending.pop() {
+        for member_id in principals
+            .get(id)
+            .and_then(|principal| principal.members())
+            .unwrap_or_default()
+        {
+            if visited.contains(&member_id.as_str()) {
+                continue;
+            }
+            visited.push(member_id);
+            if let Some(member) = principals.get(member_id.as_str()) {
+                if matches!(member.ptype(), Some(Type::Group | Type::List)) {
+                    pending.push(member_id);
+                } else if let Some(email) = member.email() {
+                    recipients.push(email.to_string());
+                }
+            }
+        }
+    }
+
+    recipients.sort_unstable();
+    recipients
+}
+
+/// Returns the members of a group or list as an indented tree, showing the
+/// members of nested groups and lists below them.
+pub fn member_tree(principals: &HashMap<&str, &Principal>, id: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    build_member_tree(principals, id, &mut vec![id.to_string()], 0, &mut lines);
+    lines
+}
+
+fn build_member_tree(
+    principals: &HashMap<&str, &Principal>,
+    id: &str,
+    path: &mut Vec<String>,
+    depth: usize,
+    lines: &mut Vec<String>,
+) {
+    let mut members = principals
+        .get(id)
+        .and_then(|principal| principal.members())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|member_id| principals.get(member_id.as_str()).copied())
+        .collect::<Vec<_>>();
+    members.sort_by(|a, b| a.email().cmp(&b.email()));
+
+    for member in members {
+        let email = display_address(member.email().unwrap_or(""));
+        let member_id = member.id().unwrap_or("");
+        match member.ptype() {
+            Some(ptype @ (Type::Group | Type::List)) => {
+                lines.push(format!(
+                    "{}{} ({})",
+                    "  ".repeat(depth),
+                    email,
+                    type_name(ptype)
+                ));
+                if !path.iter().any(|id| id == member_id) {
+                    path.push(member_id.to_string());
+                    build_member_tree(principals, member_id, path, depth + 1, lines);
+                    path.pop();
+                }
+            }
+            Some(Type::Other) => lines.push(format!("{}{} (external)", "  ".repeat(depth), email)),
+            _ => lines.push(format!("{}{}", "  ".repeat(depth), email)),
+        }
+    }
+}
+
+/// Returns true if `target` is `id` or one of its direct or nested members.
+fn contains_member(principals: &HashMap<&str, &Principal>, id: &str, target: &str) -> bool {
+    let mut visited = vec![id];
+    let mut pending = vec![id];
+
+    while let Some(id) = pending.pop() {
+        if id == target {
+            return true;
+        }
+        for member_id in principals
+            .get(id)
+            .and_then(|principal| principal.members())
+            .unwrap_or_default()
+        {
+            if !visited.contains(&member_id.as_str()) {
+                visited.push(member_id);
+                pending.push(member_id);
+            }
+        }
+    }
+
+    false
+}
+
+/// Fetches all accounts, groups, lists and external recipients with their
+/// addresses and members.
+pub fn fetch_memberships(client: &Client) -> Vec<Principal> {
+    fetch_principals(
+        client,
+        Filter::or([
+            query::Filter::ptype(Type::Individual),
+            query::Filter::ptype(Type::Group),
+            query::Filter::ptype(Type::List),
+            query::Filter::ptype(Type::Other),
+        ]),
+        &[
+            Property::Id,
+            Property::Type,
+            Property::Email,
+            Property::Aliases,
+            Property::Members,
+        ],
+    )
+}
+
+/// Adds or removes members of a group or list. Members may be accounts,
+/// groups or lists, and additions that would create a cycle are refused.
+/// Lists also accept addresses in domains not hosted by this server, which
+/// are stored as principals of type `other`; new ones are only created when
+/// `external` is set. This relies on the server delivering list messages to
+/// the email address of members of type `other`.
+pub fn update_members(
+    client: &Client,
+    ptype: Type,
+    email: &str,
+    members: &[String],
+    add: bool,
+    external: bool,
+) {
+    let is_list = matches!(ptype, Type::List);
+    let kind = if is_list { "list" } else { "group" };
+    let member_types: &[Type] = if is_list {
+        &[Type::Individual, Type::Group, Type::List, Type::Other]
+    } else {
+        &[Type::Individual, Type::Group, Type::List]
+    };
+    let update_id = email_to_id(client, ptype, email);
+    let mut external_recipients = None;
+    let member_ids = members
+        .iter()
+        .map(|member| {
+            if is_list && add {
+                let address = normalize_address(member).unwrap_or_else(|err| {
+                    eprintln!("Error: {}.", err);
+                    std::process::exit(1);
+                });
+                if !address_domain(&address).map_or(false, |domain| domain_exists(client, domain)) {
+                    let recipients = external_recipients.get_or_insert_with(|| {
+                        fetch_principals(
+                            client,
+                            query::Filter::ptype(Type::Other).into(),
+                            &[Property::Id, Property::Email],
+                        )
+                    });
+                    return external_recipient_id(recipients, &address).ok_or(address);
+                }
+            }
+            Ok(resolve_email(client, member_types, member))
+        })
+        .collect::<Vec<_>>();
+
+    let new_recipients = member_ids
+        .iter()
+        .filter_map(|member_id| member_id.as_ref().err())
+        .collect::<Vec<_>>();
+    if !new_recipients.is_empty() && !external {
+        eprintln!(
+            "Error: {} {} not in a domain hosted by this server, use --external to add them as external recipients.",
+            new_recipients
+                .iter()
+                .map(|address| format!("'{}'", address))
+                .collect::<Vec<_>>()
+                .join(", "),
+            if new_recipients.len() == 1 { "is" } else { "are" }
+        );
+        std::process::exit(1);
+    }
+
+    if add {
+        let principals = fetch_memberships(client);
+        let principals_by_id = principals
+            .iter()
+            .filter_map(|principal| Some((principal.id()?, principal)))
+            .collect::<HashMap<_, _>>();
+        for (member, member_id) in members
+            .iter()
+            .zip(&member_ids)
+            .filter_map(|(member, member_id)| Some((member, member_id.as_ref().ok()?)))
+        {
+            if contains_member(&principals_by_id, member_id, &update_id) {
+                eprintln!(
+                    "Error: Adding '{}' to {} '{}' would create a membership cycle.",
+                    member, kind, email
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let member_ids = member_ids
+        .into_iter()
+        .map(|member_id| {
+            member_id.unwrap_or_else(|address| create_external_recipient(client, &address))
+        })
+        .collect::<Vec<_>>();
+
+    let mut request = client.build();
+    let update_request = request.set_principal().update(&update_id);
+    for member_id in &member_ids {
+        update_request.member(member_id, add);
+    }
+    request
+        .send_set_principal()
+        .unwrap_result(&format!("update {}", kind))
+        .updated(&update_id)
+        .unwrap_result(&format!("update {}", kind));
+
+    if is_list && !add {
+        remove_unused_external_recipients(client, &member_ids);
+    }
+}
+
+/// Returns the id of the existing external recipient with an address.
+fn external_recipient_id(recipients: &[Principal], address: &str) -> Option<String> {
+    recipients
+        .iter()
+        .find(|principal| {
+            principal
+                .email()
+                .map_or(false, |email| email.eq_ignore_ascii_case(address))
+        })
+        .and_then(|principal| principal.id())
+        .map(String::from)
+}
+
+/// Creates an external recipient principal for an address, returning its id.
+fn create_external_recipient(client: &Client, address: &str) -> String {
+    let mut request = client.build();
+    let create_request = request.set_principal().create();
+    create_request
+        .ptype(Type::Other)
+        .email(address)
+        .name(address);
+    let create_id = create_request.create_id().unwrap();
+    let id = request
+        .send_set_principal()
+        .unwrap_result("create external recipient")
+        .created(&create_id)
+        .unwrap_result("create external recipient")
+        .take_id();
+    eprintln!("Added external recipient '{}'.", address);
+    id
+}
+
+/// Deletes the external recipients that no longer belong to any list.
+fn remove_unused_external_recipients(client: &Client, ids: &[String]) {
+    let principals = fetch_memberships(client);
+    for principal in &principals {
+        let id = principal.id().unwrap_or("");
+        if matches!(principal.ptype(), Some(Type::Other))
+            && ids.iter().any(|removed_id| removed_id == id)
+            && !principals.iter().any(|parent| {
+                parent
+                    .members()
+                    .unwrap_or_default()
+                    .iter()
+                    .any(|member_id| member_id == id)
+            })
+        {
+            if let Err(err) = client.principal_destroy(id) {
+                eprintln!(
+                    "Warning: Failed to delete external recipient '{}': {}",
+                    principal.email().unwrap_or(""),
+                    err
+                );
+            }
+        }
+    }
+}
+
+/// Displays a group or list, showing its members as a tree or, when
+/// expanded, as the flattened list of accounts receiving its messages.
+pub fn display_members_principal(client: &Client, ptype: Type, email: &str, expand: bool) {
+    let id = email_to_id(client, ptype, email);
+    let principals = fetch_memberships(client);
+    let principals_by_id = principals
+        .iter()
+        .filter_map(|principal| Some((principal.id()?, principal)))
+        .collect::<HashMap<_, _>>();
+    let members = if expand {
+        let external = principals
+            .iter()
+            .filter(|principal| matches!(principal.ptype(), Some(Type::Other)))
+            .filter_map(|principal| principal.email())
+            .collect::<Vec<_>>();
+        expand_members(&principals_by_id, &id)
+            .iter()
+            .map(|email| {
+                if external.contains(&email.as_str()) {
+                    format!("{} (external)", display_address(email))
+                } else {
+                    display_address(email).into_owned()
+                }
+            })
+            .collect::<Vec<_>>()
+    } else {
+        member_tree(&principals_by_id, &id)
+    };
+
+    display_principal_with(
+        client,
+        &id,
+        &[Property::Email, Property::Name, Property::Description],
+        &[("Members", members.join("\n"))],
+    );
+}
+
+pub fn text_filter(ptype: Type, filter: Option<String>) -> Filter<query::Filter> {
+    if let Some(filter) = filter {
         Filter::and(vec![
             query::Filter::ptype(ptype),
             query::Filter::text(filter),
         ])
     } else {
         query::Filter::ptype(ptype).into()
-    };
+    }
+}
+
+/// Converts a quota in bytes into the value stored on the principal, where zero
+/// removes the quota. The JMAP client sends quotas as 32-bit integers.
+pub fn principal_quota(quota: u64) -> Result<Option<u32>, String> {
+    if quota > 0 {
+        u32::try_from(quota).map(Some).map_err(|_| {
+            format!(
+                "quota of {} bytes exceeds the maximum of {} bytes supported by the server API",
+                quota,
+                u32::MAX
+            )
+        })
+    } else {
+        Ok(None)
+    }
+}
+
+pub fn bulk_update(
+    client: &Client,
+    ptype: Type,
+    filter: Option<String>,
+    force: bool,
+    update: impl Fn(&mut Principal<Set>),
+) {
+    let principals = fetch_principals(
+        client,
+        text_filter(ptype, filter),
+        &[Property::Id, Property::Email],
+    );
+    if principals.is_empty() {
+        eprintln!("No matching principals found.");
+        return;
+    }
+    if !force
+        && !confirm(&format!(
+            "Will update {} principals. Continue?",
+            principals.len()
+        ))
+    {
+        eprintln!("Operation cancelled.");
+        return;
+    }
+
+    update_principals(client, &principals, |_, update_request| {
+        update(update_request)
+    });
+}
 
+/// Applies an update to each principal, sending as many requests as the
+/// server's maxObjectsInSet requires, and reports any failures.
+pub fn update_principals(
+    client: &Client,
+    principals: &[Principal],
+    update: impl Fn(&Principal, &mut Principal<Set>),
+) {
+    let chunk_size = client
+        .session()
+        .core_capabilities()
+        .map(|c| c.max_objects_in_set())
+        .unwrap_or(100)
+        .max(1);
+    let mut total_updated = 0;
+    let mut failures = Vec::new();
+
+    for chunk in principals.chunks(chunk_size) {
+        let mut request = client.build();
+        let set_request = request.set_principal();
+        for principal in chunk {
+            update(principal, set_request.update(principal.id().unwrap()));
+        }
+        let mut response = request
+            .send_set_principal()
+            .unwrap_result("update principals");
+        for principal in chunk {
+            if let Err(err) = response.updated(principal.id().unwrap()) {
+                failures.push(format!(
+                    "Failed to update '{}': {}",
+                    principal.email().unwrap_or(""),
+                    err
+                ));
+            } else {
+                total_updated += 1;
+            }
+        }
+    }
+
+    eprintln!(
+        "\nSuccessfully updated {} of {} principals.",
+        total_updated,
+        principals.len()
+    );
+    if !failures.is_empty() {
+        eprintln!("There were {} failures:\n", failures.len());
+        for failure in &failures {
+            eprintln!("{}", failure);
+        }
+    }
+}
+
+pub fn fetch_principals(
+    client: &Client,
+    filter: Filter<query::Filter>,
+    properties: &[Property],
+) -> Vec<Principal> {
     let mut request = client.build();
     let query_ref = request
         .query_principal()
@@ -80,7 +692,7 @@ pub fn list_principals(
         .get_principal()
         .ids_ref(query_ref)
         .properties(properties.iter().cloned());
-    let results = request
+    request
         .send()
         .unwrap_result("list principals")
         .unwrap_method_responses()
@@ -91,36 +703,250 @@ pub fn list_principals(
         })
         .unwrap_get_principal()
         .unwrap_result("list principals")
-        .take_list();
+        .take_list()
+}
 
-    if !results.is_empty() {
-        // Build table
-        let mut table = Table::new();
-        table.add_row(Row::new(
-            properties
-                .iter()
-                .map(|p| Cell::new(p.table_name()).with_style(Attr::Bold))
-                .collect(),
-        ));
+/// Fetches the accounts, groups and lists, the principals that own addresses.
+pub fn fetch_addressable_principals(client: &Client, properties: &[Property]) -> Vec<Principal> {
+    fetch_principals(
+        client,
+        Filter::or([
+            query::Filter::ptype(Type::Individual),
+            query::Filter::ptype(Type::Group),
+            query::Filter::ptype(Type::List),
+        ]),
+        properties,
+    )
+}
 
-        for principal in &results {
-            table.add_row(Row::new(build_cells(client, principal, properties)));
+/// Returns true if an address is one of the aliases of a principal.
+pub fn has_alias(principal: &Principal, address: &str) -> bool {
+    principal
+        .aliases()
+        .into_iter()
+        .flatten()
+        .any(|alias| alias.eq_ignore_ascii_case(address))
+}
+
+pub fn principals_in_domain(
+    client: &Client,
+    ptype: Type,
+    domain: &str,
+    properties: &[Property],
+) -> Vec<Principal> {
+    let mut properties = properties.to_vec();
+    if !properties.contains(&Property::Email) {
+        properties.push(Property::Email);
+    }
+
+    fetch_principals(client, query::Filter::ptype(ptype).into(), &properties)
+        .into_iter()
+        .filter(|principal| {
+            principal
+                .email()
+                .and_then(|email| email.rsplit_once('@'))
+                .map_or(false, |(_, email_domain)| {
+                    email_domain.eq_ignore_ascii_case(domain)
+                })
+        })
+        .collect()
+}
+
+pub fn remove_from_memberships(client: &Client, id: &str) {
+    let parents = fetch_principals(
+        client,
+        Filter::or([
+            query::Filter::ptype(Type::Group),
+            query::Filter::ptype(Type::List),
+        ]),
+        &[Property::Id, Property::Email, Property::Members],
+    )
+    .into_iter()
+    .filter(|principal| {
+        principal
+            .members()
+            .map_or(false, |members| members.iter().any(|member| member == id))
+    })
+    .collect::<Vec<_>>();
+
+    if parents.is_empty() {
+        return;
+    }
+
+    let mut request = client.build();
+    let set_request = request.set_principal();
+    for parent in &parents {
+        set_request.update(parent.id().unwrap()).member(id, false);
+    }
+    let mut response = request
+        .send_set_principal()
+        .unwrap_result("update memberships");
+    for parent in &parents {
+        if let Err(err) = response.updated(parent.id().unwrap()) {
+            eprintln!(
+                "Warning: Failed to remove membership from '{}': {}",
+                parent.email().unwrap_or(""),
+                err
+            );
         }
+    }
+}
 
-        eprintln!();
-        table.printstd();
+/// Deletes a group or mailing list after confirmation, removing it from
+/// any group or list it belongs to and from the configured account defaults.
+pub fn delete_group(client: &Client, ptype: Type, email: &str, force: bool, show_members: bool) {
+    let kind = if matches!(ptype, Type::Group) {
+        "Group"
+    } else {
+        "List"
+    };
+    let id = email_to_id(client, ptype, email);
+    let principal = client
+        .principal_get(
+            &id,
+            [
+                Property::Id,
+                Property::Email,
+                Property::Members,
+                Property::Aliases,
+            ]
+            .into(),
+        )
+        .unwrap_result("fetch principal")
+        .unwrap_result(&format!("find {}", kind.to_lowercase()));
+    let members = principal.members().unwrap_or_default();
+
+    if show_members && !members.is_empty() {
+        let names = principal_names(client, members.iter());
+        let mut members = members
+            .iter()
+            .map(|member| {
+                names
+                    .get(member)
+                    .map_or(member.as_str(), |name| name.as_str())
+            })
+            .collect::<Vec<_>>();
+        members.sort_unstable();
+        eprintln!("\nThe following members will lose their membership:\n");
+        for member in members {
+            eprintln!("  {}", display_address(member));
+        }
+    }
+    let aliases = principal.aliases().unwrap_or_default();
+    if !aliases.is_empty() {
+        eprintln!(
+            "\nThe following aliases will be removed: {}.",
+            aliases
+                .iter()
+                .map(|alias| display_address(alias))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
     }
 
-    eprintln!(
-        "\n\n{} record{} found.\n",
-        results.len(),
-        if results.len() == 1 { "" } else { "s" }
-    );
+    if !force
+        && !confirm(&format!(
+            "{} '{}' with {} member{} will be permanently deleted. Continue?",
+            kind,
+            email,
+            members.len(),
+            if members.len() == 1 { "" } else { "s" }
+        ))
+    {
+        eprintln!("Operation cancelled.");
+        return;
+    }
+
+    remove_from_memberships(client, &id);
+    client
+        .principal_destroy(&id)
+        .unwrap_result(&format!("delete {}", kind.to_lowercase()));
+    if matches!(ptype, Type::List) {
+        remove_unused_external_recipients(client, members);
+    }
+
+    // Stop adding new accounts to a deleted group
+    let mut config = Config::load();
+    let mut has_changes = false;
+    for defaults in config
+        .templates
+        .values_mut()
+        .chain(config.domains.values_mut())
+    {
+        let num_groups = defaults.groups.len();
+        defaults
+            .groups
+            .retain(|group| !group.eq_ignore_ascii_case(principal.email().unwrap_or(email)));
+        has_changes |= num_groups != defaults.groups.len();
+    }
+    if has_changes {
+        config.save();
+    }
+
+    eprintln!("{} '{}' successfully deleted.", kind, email);
+}
+
+pub fn add_to_groups<'x>(
+    client: &Client,
+    memberships: impl IntoIterator<Item = (&'x str, &'x str)>,
+) {
+    let mut group_members: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (member_id, group) in memberships {
+        group_members.entry(group).or_default().push(member_id);
+    }
+
+    let mut request = client.build();
+    let set_request = request.set_principal();
+    let mut group_ids = Vec::with_capacity(group_members.len());
+    for (group, member_ids) in group_members {
+        if let Some(group_id) = client
+            .principal_query(
+                Filter::and([
+                    query::Filter::ptype(Type::Group),
+                    query::Filter::email(group),
+                ])
+                .into(),
+                None::<Vec<_>>,
+            )
+            .unwrap_result("query principals")
+            .take_ids()
+            .pop()
+        {
+            let update_request = set_request.update(&group_id);
+            for member_id in member_ids {
+                update_request.member(member_id, true);
+            }
+            group_ids.push((group, group_id));
+        } else {
+            eprintln!("Warning: Group '{}' does not exist.", group);
+        }
+    }
+
+    if !group_ids.is_empty() {
+        let mut response = request.send_set_principal().unwrap_result("update groups");
+        for (group, group_id) in group_ids {
+            if let Err(err) = response.updated(&group_id) {
+                eprintln!(
+                    "Warning: Failed to add members to group '{}': {}",
+                    group, err
+                );
+            }
+        }
+    }
 }
 
 pub fn display_principal(client: &Client, id: &str, properties: &[Property]) {
+    display_principal_with(client, id, properties, &[]);
+}
+
+pub fn display_principal_with(
+    client: &Client,
+    id: &str,
+    properties: &[Property],
+    extra_rows: &[(&str, String)],
+) {
     if let Some(principal) = client
-        .principal_get(id, properties.iter().cloned().into())
+        .principal_get(id, with_type_property(properties).into())
         .unwrap_result("fetch principal")
     {
         eprintln!();
@@ -134,6 +960,12 @@ pub fn display_principal(client: &Client, id: &str, properties: &[Property]) {
                 value,
             ]));
         }
+        for (name, value) in extra_rows {
+            table.add_row(Row::new(vec![
+                Cell::new(name).with_style(Attr::Bold),
+                Cell::new(value),
+            ]));
+        }
         table.printstd();
         eprintln!();
     } else {
@@ -142,14 +974,26 @@ pub fn display_principal(client: &Client, id: &str, properties: &[Property]) {
     }
 }
 
+/// Adds the type property, which `build_cells` uses to decide how to display names.
+fn with_type_property(properties: &[Property]) -> Vec<Property> {
+    let mut properties = properties.to_vec();
+    if !properties.contains(&Property::Type) {
+        properties.push(Property::Type);
+    }
+    properties
+}
+
 fn build_cells(client: &Client, principal: &Principal, properties: &[Property]) -> Vec<Cell> {
     let mut cells = Vec::with_capacity(properties.len());
     for property in properties.iter() {
         let value: Cow<str> = match property {
             Property::Id => principal.id().unwrap_or("").into(),
+            Property::Name if matches!(principal.ptype(), Some(Type::Domain)) => {
+                display_domain(principal.name().unwrap_or(""))
+            }
             Property::Name => principal.name().unwrap_or("").into(),
             Property::Description => principal.description().unwrap_or("").into(),
-            Property::Email => principal.email().unwrap_or("").into(),
+            Property::Email => display_address(principal.email().unwrap_or("")),
             Property::Timezone => principal.timezone().unwrap_or("").into(),
             Property::Capabilities => principal
                 .capabilities()
@@ -157,7 +1001,13 @@ fn build_cells(client: &Client, principal: &Principal, properties: &[Property])
                 .unwrap_or_else(|| "".into()),
             Property::Aliases => principal
                 .aliases()
-                .map(|c| c.join(", ").into())
+                .map(|c| {
+                    c.iter()
+                        .map(|alias| display_address(alias))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                        .into()
+                })
                 .unwrap_or_else(|| "".into()),
             Property::DKIM => principal
                 .dkim()
@@ -195,6 +1045,25 @@ fn build_cells(client: &Client, principal: &Principal, properties: &[Property])
                     "".into()
                 }
             }
+            Property::ACL => {
+                if let Some(acl) = principal.acl() {
+                    let names = principal_names(client, acl.keys());
+                    let mut acl = acl
+                        .iter()
+                        .map(|(id, rights)| {
+                            format!(
+                                "{}: {}",
+                                names.get(id).map(|n| n.as_str()).unwrap_or(id),
+                                rights.iter().map(acl_name).collect::<Vec<_>>().join(", ")
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    acl.sort_unstable();
+                    acl.join("\n").into()
+                } else {
+                    "".into()
+                }
+            }
             _ => unreachable!(),
         };
 
@@ -202,3 +1071,119 @@ fn build_cells(client: &Client, principal: &Principal, properties: &[Property])
     }
     cells
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use jmap_client::principal::Principal;
+
+    use super::{contains_member, edit_distance, expand_members, member_tree};
+
+    fn principal(id: &str, ptype: &str, email: &str, members: &[&str]) -> Principal {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "type": ptype,
+            "email": email,
+            "members": members,
+        }))
+        .unwrap()
+    }
+
+    /// Returns a group and a list that are members of each other, with the
+    /// group also listing itself as a member.
+    fn memberships() -> Vec<Principal> {
+        vec![
+            principal(
+                "team",
+                "group",
+                "team@example.com",
+                &["alice", "sales", "team"],
+            ),
+            principal(
+                "sales",
+                "list",
+                "sales@example.com",
+                &["bob", "team", "ext"],
+            ),
+            principal("alice", "individual", "alice@example.com", &[]),
+            principal("bob", "individual", "bob@example.com", &[]),
+            principal("ext", "other", "ext@example.org", &[]),
+            principal("solo", "group", "solo@example.com", &["solo"]),
+        ]
+    }
+
+    fn by_id(principals: &[Principal]) -> HashMap<&str, &Principal> {
+        principals
+            .iter()
+            .filter_map(|principal| Some((principal.id()?, principal)))
+            .collect()
+    }
+
+    #[test]
+    fn edit_distances() {
+        for (a, b, distance) in [
+            ("", "", 0),
+            ("john", "john", 0),
+            ("john", "", 4),
+            ("", "john", 4),
+            ("jon", "john", 1),
+            ("john", "jonh", 2),
+            ("kitten", "sitting", 3),
+            ("münchen", "munchen", 1),
+        ] {
+            assert_eq!(edit_distance(a, b), distance, "{} {}", a, b);
+            assert_eq!(edit_distance(b, a), distance, "{} {}", b, a);
+        }
+    }
+
+    #[test]
+    fn expand_nested_members() {
+        let principals = memberships();
+        let principals = by_id(&principals);
+        let recipients = [
+            "alice@example.com".to_string(),
+            "bob@example.com".to_string(),
+            "ext@example.org".to_string(),
+        ];
+        assert_eq!(expand_members(&principals, "team"), recipients);
+        assert_eq!(expand_members(&principals, "sales"), recipients);
+        assert!(expand_members(&principals, "solo").is_empty());
+        assert!(expand_members(&principals, "alice").is_empty());
+    }
+
+    #[test]
+    fn detect_membership_cycles() {
+        let principals = memberships();
+        let principals = by_id(&principals);
+        assert!(contains_member(&principals, "team", "team"));
+        assert!(contains_member(&principals, "team", "bob"));
+        assert!(contains_member(&principals, "sales", "team"));
+        assert!(contains_member(&principals, "solo", "solo"));
+        assert!(!contains_member(&principals, "alice", "team"));
+        assert!(!contains_member(&principals, "team", "solo"));
+        assert!(!contains_member(&principals, "unknown", "team"));
+    }
+
+    #[test]
+    fn build_member_trees() {
+        let principals = memberships();
+        let principals = by_id(&principals);
+        assert_eq!(
+            member_tree(&principals, "team"),
+            [
+                "alice@example.com",
+                "sales@example.com (list)",
+                "  bob@example.com",
+                "  ext@example.org (external)",
+                "  team@example.com (group)",
+                "team@example.com (group)",
+            ]
+        );
+        assert_eq!(
+            member_tree(&principals, "solo"),
+            ["solo@example.com (group)"]
+        );
+        assert!(member_tree(&principals, "alice").is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Command Line Interface.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::UnwrapResult;
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub templates: BTreeMap<String, AccountDefaults>,
+    #[serde(default)]
+    pub domains: BTreeMap<String, AccountDefaults>,
+    #[serde(default)]
+    pub dkim: BTreeMap<String, DkimRotation>,
+    #[serde(default)]
+    pub alias_domains: BTreeMap<String, String>,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct DkimRotation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending: Option<PendingDkimKey>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activated_at: Option<i64>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PendingDkimKey {
+    pub selector: String,
+    pub key_file: PathBuf,
+    pub created_at: i64,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct AccountDefaults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        if let Some(path) = std::env::var_os("STALWART_CLI_CONFIG") {
+            PathBuf::from(path)
+        } else {
+            PathBuf::from(
+                std::env::var_os("HOME")
+                    .or_else(|| std::env::var_os("USERPROFILE"))
+                    .unwrap_or_default(),
+            )
+            .join(".stalwart")
+            .join("cli.json")
+        }
+    }
+
+    pub fn load() -> Self {
+        let path = Self::path();
+        if path.exists() {
+            serde_json::from_slice(&std::fs::read(&path).unwrap_result("read configuration file"))
+                .unwrap_result("parse configuration file")
+        } else {
+            Config::default()
+        }
+    }
+
+    pub fn save(&self) {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap_result("create configuration directory");
+        }
+        std::fs::write(
+            &path,
+            serde_json::to_vec_pretty(self).unwrap_result("serialize configuration"),
+        )
+        .unwrap_result("write configuration file");
+    }
+
+    pub fn account_defaults(&self, template: Option<&str>, domain: &str) -> AccountDefaults {
+        let mut defaults = if let Some(template) = template {
+            self.templates.get(template).cloned().unwrap_or_else(|| {
+                eprintln!("Error: Template '{}' does not exist.", template);
+                std::process::exit(1);
+            })
+        } else {
+            AccountDefaults::default()
+        };
+
+        if let Some(domain_defaults) = self.domains.get(&domain.to_lowercase()) {
+            // Domain timezones are stored on the server, any local value is ignored
+            defaults.merge(&AccountDefaults {
+                timezone: None,
+                ..domain_defaults.clone()
+            });
+        }
+
+        defaults
+    }
+
+    /// Returns the addresses an account receives through alias domains.
+    pub fn alias_domain_addresses(&self, email: &str) -> Vec<String> {
+        if let Some((local_part, domain)) = email.rsplit_once('@') {
+            self.alias_domains
+                .iter()
+                .filter(|(_, target)| target.eq_ignore_ascii_case(domain))
+                .map(|(alias_domain, _)| format!("{}@{}", local_part, alias_domain))
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl AccountDefaults {
+    pub fn merge(&mut self, other: &AccountDefaults) {
+        if self.description.is_none() {
+            self.description = other.description.clone();
+        }
+        if self.quota.is_none() {
+            self.quota = other.quota;
+        }
+        if self.timezone.is_none() {
+            self.timezone = other.timezone.clone();
+        }
+        for group in &other.groups {
+            if !self.groups.contains(group) {
+                self.groups.push(group.clone());
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.description.is_none()
+            && self.quota.is_none()
+            && self.timezone.is_none()
+            && self.groups.is_empty()
+    }
+}
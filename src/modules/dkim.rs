@@ -0,0 +1,521 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Command Line Interface.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{fs, io::Write, path::Path};
+
+use chrono::{TimeZone, Utc};
+
+use jmap_client::{
+    client::Client,
+    principal::{Property, DKIM},
+};
+
+use rsa::{
+    pkcs1::{DecodeRsaPrivateKey, EncodeRsaPrivateKey, LineEnding},
+    pkcs8::{DecodePrivateKey, EncodePublicKey},
+    PublicKeyParts, RsaPrivateKey, RsaPublicKey,
+};
+
+use ring::{
+    rand::SystemRandom,
+    signature::{Ed25519KeyPair, KeyPair},
+};
+
+use super::{
+    cli::{DkimAlgorithm, DkimCommands},
+    config::{Config, PendingDkimKey},
+    domain::domain_to_id,
+    UnwrapResult,
+};
+
+/// RFC 8301 requires signers to use RSA keys of at least 1024 bits and
+/// recommends 2048 bits.
+const MIN_RSA_BITS: usize = 1024;
+pub const RECOMMENDED_RSA_BITS: usize = 2048;
+
+pub fn cmd_dkim(client: Client, command: DkimCommands) {
+    match command {
+        DkimCommands::Rotate {
+            name,
+            algorithm,
+            selector,
+            output,
+        } => {
+            let name = name.to_ascii_lowercase();
+            let current_selector = client
+                .principal_get(&domain_to_id(&client, &name), [Property::DKIM].into())
+                .unwrap_result("fetch domain")
+                .unwrap_result("find domain")
+                .dkim()
+                .and_then(|dkim| dkim.selector())
+                .map(|selector| selector.to_string());
+            let selector = selector.unwrap_or_else(default_selector);
+            if current_selector.as_deref() == Some(selector.as_str()) {
+                eprintln!(
+                    "Error: Selector '{}' is already in use by domain '{}', choose a different one with --selector.",
+                    selector, name
+                );
+                std::process::exit(1);
+            }
+
+            let dkim_key = DkimKey::generate(algorithm.unwrap_or(DkimAlgorithm::Rsa2048));
+            write_private_key(&output, &dkim_key.private_key);
+            let mut config = Config::load();
+            let rotation = config.dkim.entry(name.clone()).or_default();
+            if let Some(pending) = &rotation.pending {
+                eprintln!(
+                    "Warning: Replacing the pending key with selector '{}'.",
+                    pending.selector
+                );
+            }
+            rotation.pending = Some(PendingDkimKey {
+                selector: selector.clone(),
+                key_file: fs::canonicalize(&output).unwrap_or_else(|_| output.clone()),
+                created_at: Utc::now().timestamp(),
+            });
+            config.save();
+
+            eprintln!(
+                "Generated {} DKIM key with selector '{}' for domain '{}', the private key was written to '{}'.",
+                dkim_key.description(),
+                selector,
+                name,
+                output.display()
+            );
+            eprintln!(
+                "\nPublish the following DNS record and run 'domain dkim activate {}' once it has propagated:\n",
+                name
+            );
+            println!("{}", dkim_key.dns_txt_record(&selector, &name));
+        }
+        DkimCommands::Activate {
+            name,
+            expiration_dkim,
+        } => {
+            let name = name.to_ascii_lowercase();
+            let mut config = Config::load();
+            let pending = config
+                .dkim
+                .get_mut(&name)
+                .and_then(|rotation| rotation.pending.take())
+                .unwrap_or_else(|| {
+                    eprintln!(
+                        "Error: Domain '{}' has no pending DKIM key, run 'domain dkim rotate {}' first.",
+                        name, name
+                    );
+                    std::process::exit(1);
+                });
+
+            let dkim_key = DkimKey::from_file(&pending.key_file).unwrap_or_else(|err| {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            });
+
+            let update_id = domain_to_id(&client, &name);
+            let domain = client
+                .principal_get(&update_id, [Property::DKIM].into())
+                .unwrap_result("fetch domain")
+                .unwrap_result("find domain");
+            let previous_selector = domain
+                .dkim()
+                .and_then(|dkim| dkim.selector())
+                .map(|selector| selector.to_string());
+            let expiration = expiration_dkim
+                .map(|s| s as i64)
+                .or_else(|| domain.dkim().and_then(|dkim| dkim.expiration()));
+
+            let mut request = client.build();
+            request
+                .set_principal()
+                .update(&update_id)
+                .secret(dkim_key.private_key)
+                .dkim(DKIM::new(Some(pending.selector.clone()), expiration));
+            request
+                .send_set_principal()
+                .unwrap_result("update domain")
+                .updated(&update_id)
+                .unwrap_result("update domain");
+
+            config.dkim.entry(name.clone()).or_default().activated_at =
+                Some(Utc::now().timestamp());
+            config.save();
+
+            eprintln!(
+                "Domain '{}' is now signing with DKIM selector '{}'.",
+                name, pending.selector
+            );
+            if let Some(previous_selector) = previous_selector {
+                eprintln!(
+                    "Keep the DNS record for selector '{}' published until messages signed with it have been delivered.",
+                    previous_selector
+                );
+            }
+            eprintln!(
+                "The private key is now stored on the server, '{}' can be deleted.",
+                pending.key_file.display()
+            );
+        }
+    }
+}
+
+/// Writes a private key to a new file that only its owner can read.
+fn write_private_key(path: &Path, private_key: &str) {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(private_key.as_bytes()))
+        .unwrap_or_else(|err| {
+            eprintln!("Error: Failed to write '{}': {}", path.display(), err);
+            std::process::exit(1);
+        });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DkimKeyType {
+    Rsa,
+    Ed25519,
+}
+
+pub struct DkimKey {
+    pub key_type: DkimKeyType,
+    pub bits: usize,
+    pub private_key: String,
+    pub public_key: Vec<u8>,
+}
+
+impl DkimKey {
+    pub fn generate(algorithm: DkimAlgorithm) -> Self {
+        match algorithm {
+            DkimAlgorithm::Rsa2048 | DkimAlgorithm::Rsa4096 => {
+                let bits = if algorithm == DkimAlgorithm::Rsa2048 {
+                    2048
+                } else {
+                    4096
+                };
+                let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), bits)
+                    .unwrap_result("generate RSA key");
+                DkimKey {
+                    key_type: DkimKeyType::Rsa,
+                    bits,
+                    public_key: RsaPublicKey::from(&private_key)
+                        .to_public_key_der()
+                        .unwrap_result("encode RSA public key")
+                        .as_bytes()
+                        .to_vec(),
+                    private_key: private_key
+                        .to_pkcs1_pem(LineEnding::LF)
+                        .unwrap_result("encode RSA private key")
+                        .to_string(),
+                }
+            }
+            DkimAlgorithm::Ed25519 => {
+                let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+                    .map_err(|_| "unspecified error")
+                    .unwrap_result("generate Ed25519 key");
+                let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+                    .map_err(|err| err.to_string())
+                    .unwrap_result("generate Ed25519 key");
+                DkimKey {
+                    key_type: DkimKeyType::Ed25519,
+                    bits: 256,
+                    private_key: pem_encode("PRIVATE KEY", pkcs8.as_ref()),
+                    public_key: key_pair.public_key().as_ref().to_vec(),
+                }
+            }
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read '{}': {}", path.display(), err))?;
+        Self::from_pem(&contents)
+            .map_err(|err| format!("Invalid DKIM private key '{}': {}", path.display(), err))
+    }
+
+    pub fn from_pem(contents: &str) -> Result<Self, String> {
+        let label = contents
+            .lines()
+            .find_map(|line| {
+                line.trim()
+                    .strip_prefix("-----BEGIN ")
+                    .and_then(|line| line.strip_suffix("-----"))
+            })
+            .ok_or("file is not PEM encoded")?;
+
+        let private_key = match label {
+            "RSA PRIVATE KEY" if contents.contains("ENCRYPTED") => {
+                return Err("passphrase-protected keys are not supported".to_string());
+            }
+            "RSA PRIVATE KEY" => RsaPrivateKey::from_pkcs1_pem(contents)
+                .map_err(|err| format!("failed to parse PKCS#1 RSA key: {}", err))?,
+            "PRIVATE KEY" => match RsaPrivateKey::from_pkcs8_pem(contents) {
+                Ok(private_key) => private_key,
+                Err(_) => {
+                    let der = pem_decode(contents)?;
+                    let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der)
+                        .map_err(|_| "unsupported PKCS#8 key, expected RSA or Ed25519")?;
+                    return Ok(DkimKey {
+                        key_type: DkimKeyType::Ed25519,
+                        bits: 256,
+                        private_key: contents.to_string(),
+                        public_key: key_pair.public_key().as_ref().to_vec(),
+                    });
+                }
+            },
+            "ENCRYPTED PRIVATE KEY" => {
+                return Err("passphrase-protected keys are not supported".to_string());
+            }
+            "CERTIFICATE" => {
+                return Err("file contains a certificate, expected a private key".to_string());
+            }
+            "PUBLIC KEY" | "RSA PUBLIC KEY" => {
+                return Err("file contains a public key, expected a private key".to_string());
+            }
+            label => {
+                return Err(format!("unsupported PEM block '{}'", label));
+            }
+        };
+
+        let bits = private_key.n().bits();
+        if bits < MIN_RSA_BITS {
+            return Err(format!(
+                "RSA key is {} bits, at least {} bits are required",
+                bits, MIN_RSA_BITS
+            ));
+        }
+        Ok(DkimKey {
+            key_type: DkimKeyType::Rsa,
+            bits,
+            public_key: RsaPublicKey::from(&private_key)
+                .to_public_key_der()
+                .map_err(|err| format!("failed to encode RSA public key: {}", err))?
+                .as_bytes()
+                .to_vec(),
+            private_key: contents.to_string(),
+        })
+    }
+
+    pub fn description(&self) -> String {
+        match self.key_type {
+            DkimKeyType::Rsa => format!("RSA {}-bit", self.bits),
+            DkimKeyType::Ed25519 => "Ed25519".to_string(),
+        }
+    }
+
+    pub fn dns_record_value(&self) -> String {
+        format!(
+            "v=DKIM1; k={}; p={}",
+            match self.key_type {
+                DkimKeyType::Rsa => "rsa",
+                DkimKeyType::Ed25519 => "ed25519",
+            },
+            base64::encode(&self.public_key)
+        )
+    }
+
+    pub fn dns_txt_record(&self, selector: &str, domain: &str) -> String {
+        format!(
+            "{}._domainkey.{}. IN TXT {}",
+            selector,
+            domain,
+            txt_strings(&self.dns_record_value())
+        )
+    }
+}
+
+pub fn default_selector() -> String {
+    Utc::now().format("%Y%m%d").to_string()
+}
+
+/// Returns the creation date of a key from a selector generated by `default_selector`.
+pub fn selector_date(selector: &str) -> Option<i64> {
+    chrono::NaiveDate::parse_from_str(selector, "%Y%m%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date| Utc.from_utc_datetime(&date).timestamp())
+}
+
+/// Splits a TXT record value into quoted strings of at most 255 characters.
+pub fn txt_strings(value: &str) -> String {
+    let strings = value
+        .as_bytes()
+        .chunks(255)
+        .map(|chunk| format!("\"{}\"", String::from_utf8_lossy(chunk)))
+        .collect::<Vec<_>>();
+    if strings.len() > 1 {
+        format!("( {} )", strings.join(" "))
+    } else {
+        strings.join(" ")
+    }
+}
+
+fn pem_encode(label: &str, der: &[u8]) -> String {
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in base64::encode(der).as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap());
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+fn pem_decode(contents: &str) -> Result<Vec<u8>, String> {
+    base64::decode(
+        contents
+            .lines()
+            .map(|line| line.trim())
+            .skip_while(|line| !line.starts_with("-----BEGIN "))
+            .skip(1)
+            .take_while(|line| !line.starts_with("-----END "))
+            .collect::<String>(),
+    )
+    .map_err(|err| format!("invalid PEM encoding: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use rsa::{
+        pkcs1::{EncodeRsaPrivateKey, EncodeRsaPublicKey, LineEnding},
+        pkcs8::{EncodePrivateKey, EncodePublicKey},
+        RsaPrivateKey, RsaPublicKey,
+    };
+
+    use super::{pem_encode, txt_strings, DkimAlgorithm, DkimKey, DkimKeyType};
+
+    fn rsa_key(bits: usize) -> RsaPrivateKey {
+        RsaPrivateKey::new(&mut rand::thread_rng(), bits).unwrap()
+    }
+
+    #[test]
+    fn parse_rsa_keys() {
+        let private_key = rsa_key(1024);
+        let public_key = RsaPublicKey::from(&private_key)
+            .to_public_key_der()
+            .unwrap()
+            .as_bytes()
+            .to_vec();
+        for pem in [
+            private_key
+                .to_pkcs1_pem(LineEnding::LF)
+                .unwrap()
+                .to_string(),
+            private_key
+                .to_pkcs8_pem(LineEnding::CRLF)
+                .unwrap()
+                .to_string(),
+        ] {
+            let key = DkimKey::from_pem(&pem).unwrap();
+            assert_eq!(key.key_type, DkimKeyType::Rsa);
+            assert_eq!(key.bits, 1024);
+            assert_eq!(key.public_key, public_key);
+            assert_eq!(key.private_key, pem);
+        }
+    }
+
+    #[test]
+    fn parse_generated_ed25519_key() {
+        let generated = DkimKey::generate(DkimAlgorithm::Ed25519);
+        let key = DkimKey::from_pem(&generated.private_key).unwrap();
+        assert_eq!(key.key_type, DkimKeyType::Ed25519);
+        assert_eq!(key.public_key, generated.public_key);
+        assert!(key.dns_record_value().starts_with("v=DKIM1; k=ed25519; p="));
+    }
+
+    #[test]
+    fn reject_short_rsa_keys() {
+        for bits in [512, 1023] {
+            let pem = rsa_key(bits).to_pkcs1_pem(LineEnding::LF).unwrap();
+            assert_eq!(
+                DkimKey::from_pem(&pem).err(),
+                Some(format!(
+                    "RSA key is {} bits, at least 1024 bits are required",
+                    bits
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn reject_unsupported_pem_blocks() {
+        let public_key = RsaPublicKey::from(&rsa_key(1024));
+        let encrypted_pkcs1 = rsa_key(1024)
+            .to_pkcs1_pem(LineEnding::LF)
+            .unwrap()
+            .replacen(
+                "-----\n",
+                "-----\nProc-Type: 4,ENCRYPTED\nDEK-Info: AES-128-CBC,00000000000000000000000000000000\n\n",
+                1,
+            );
+        for (pem, error) in [
+            ("not a key".to_string(), "file is not PEM encoded"),
+            (
+                encrypted_pkcs1,
+                "passphrase-protected keys are not supported",
+            ),
+            (
+                pem_encode("ENCRYPTED PRIVATE KEY", b"key"),
+                "passphrase-protected keys are not supported",
+            ),
+            (
+                pem_encode("CERTIFICATE", b"certificate"),
+                "file contains a certificate, expected a private key",
+            ),
+            (
+                public_key.to_public_key_pem(LineEnding::LF).unwrap(),
+                "file contains a public key, expected a private key",
+            ),
+            (
+                public_key.to_pkcs1_pem(LineEnding::LF).unwrap(),
+                "file contains a public key, expected a private key",
+            ),
+            (
+                pem_encode("EC PRIVATE KEY", b"key"),
+                "unsupported PEM block 'EC PRIVATE KEY'",
+            ),
+            (
+                pem_encode("PRIVATE KEY", b"key"),
+                "unsupported PKCS#8 key, expected RSA or Ed25519",
+            ),
+        ] {
+            assert_eq!(DkimKey::from_pem(&pem).err().as_deref(), Some(error));
+        }
+    }
+
+    #[test]
+    fn split_txt_strings() {
+        assert_eq!(txt_strings("v=DKIM1; p=abc"), "\"v=DKIM1; p=abc\"");
+        let value = "a".repeat(255);
+        assert_eq!(txt_strings(&value), format!("\"{}\"", value));
+        let value = format!("{}{}{}", "a".repeat(255), "b".repeat(255), "c");
+        assert_eq!(
+            txt_strings(&value),
+            format!("( \"{}\" \"{}\" \"c\" )", "a".repeat(255), "b".repeat(255))
+        );
+    }
+}
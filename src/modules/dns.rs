@@ -0,0 +1,556 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Command Line Interface.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::net::{IpAddr, SocketAddr};
+
+use jmap_client::{
+    client::Client,
+    principal::{Principal, Property},
+};
+use prettytable::{Attr, Cell, Row, Table};
+use ring::digest::{digest, SHA256};
+use serde_json::json;
+use trust_dns_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    error::ResolveErrorKind,
+    Resolver,
+};
+
+use super::{
+    cli::DnsFormat,
+    dkim::{txt_strings, DkimKey},
+    domain::domain_to_id,
+    UnwrapResult,
+};
+
+pub struct DnsRecord {
+    pub name: String,
+    pub record_type: &'static str,
+    pub value: String,
+}
+
+impl DnsRecord {
+    fn new(name: impl Into<String>, record_type: &'static str, value: impl Into<String>) -> Self {
+        DnsRecord {
+            name: name.into(),
+            record_type,
+            value: value.into(),
+        }
+    }
+
+    pub fn to_bind(&self) -> String {
+        format!(
+            "{} IN {} {}",
+            self.name,
+            self.record_type,
+            if self.record_type == "TXT" {
+                txt_strings(&self.value)
+            } else {
+                self.value.clone()
+            }
+        )
+    }
+}
+
+pub struct ServerAddress {
+    pub hostname: String,
+    pub port: u16,
+}
+
+impl ServerAddress {
+    pub fn from_url(url: &str) -> Self {
+        let url = reqwest::Url::parse(url).unwrap_result("parse server URL");
+        ServerAddress {
+            hostname: url
+                .host_str()
+                .unwrap_or_else(|| {
+                    eprintln!("Error: Server URL '{}' does not contain a hostname.", url);
+                    std::process::exit(1);
+                })
+                .to_ascii_lowercase(),
+            port: url.port_or_known_default().unwrap_or(443),
+        }
+    }
+}
+
+pub fn cmd_dns(client: &Client, url: &str, name: &str, format: DnsFormat) {
+    let server = ServerAddress::from_url(url);
+    let domain = client
+        .principal_get(
+            &domain_to_id(client, name),
+            [Property::Name, Property::Secret, Property::DKIM].into(),
+        )
+        .unwrap_result("fetch domain")
+        .unwrap_result("find domain");
+    let records = domain_records(&domain, &name.to_ascii_lowercase(), &server);
+
+    match format {
+        DnsFormat::Bind => {
+            for record in &records {
+                println!("{}", record.to_bind());
+            }
+        }
+        DnsFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(
+                    &records
+                        .iter()
+                        .map(|record| {
+                            json!({
+                                "name": record.name,
+                                "type": record.record_type,
+                                "value": record.value,
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                )
+                .unwrap_result("serialize records")
+            );
+        }
+    }
+}
+
+pub fn cmd_verify(client: &Client, url: &str, name: &str, resolver: Option<String>) {
+    let server = ServerAddress::from_url(url);
+    let domain = client
+        .principal_get(
+            &domain_to_id(client, name),
+            [Property::Name, Property::Secret, Property::DKIM].into(),
+        )
+        .unwrap_result("fetch domain")
+        .unwrap_result("find domain");
+    let name = name.to_ascii_lowercase();
+    let resolver = build_resolver(resolver.as_deref());
+    let mut checks = Vec::new();
+
+    // MX
+    let mx_name = format!("{}.", name);
+    checks.push(match mx_records(&resolver, &mx_name) {
+        Ok(exchanges) if exchanges.is_empty() => {
+            Check::missing("MX", mx_name, format!("expected '10 {}.'", server.hostname))
+        }
+        Ok(exchanges) if exchanges.contains(&server.hostname) => {
+            Check::ok("MX", mx_name, exchanges.join(", "))
+        }
+        Ok(exchanges) => Check::mismatch(
+            "MX",
+            mx_name,
+            format!(
+                "points to {}, expected '{}'",
+                exchanges.join(", "),
+                server.hostname
+            ),
+        ),
+        Err(err) => Check::error("MX", mx_name, err),
+    });
+
+    // SPF
+    checks.push(check_txt(&resolver, "SPF", format!("{}.", name), "v=spf1"));
+
+    // DKIM, the public key can only be compared when the server returns the private key
+    if let Some(selector) = domain.dkim().and_then(|dkim| dkim.selector()) {
+        let dkim_name = format!("{}._domainkey.{}.", selector, name);
+        let dkim_key = domain_dkim(&domain, &name).map(|(_, dkim_key)| dkim_key);
+        checks.push(match txt_records(&resolver, &dkim_name) {
+            Ok(records) => {
+                let published_keys = records
+                    .iter()
+                    .filter_map(|record| dkim_public_key(record))
+                    .collect::<Vec<_>>();
+                match &dkim_key {
+                    Some(dkim_key) if published_keys.is_empty() => Check::missing(
+                        "DKIM",
+                        dkim_name,
+                        format!("expected {} key", dkim_key.description()),
+                    ),
+                    None if published_keys.is_empty() => {
+                        Check::missing("DKIM", dkim_name, "no DKIM key record found".to_string())
+                    }
+                    Some(dkim_key)
+                        if published_keys.contains(&base64::encode(&dkim_key.public_key)) =>
+                    {
+                        Check::ok("DKIM", dkim_name, format!("{} key", dkim_key.description()))
+                    }
+                    Some(_) => Check::mismatch(
+                        "DKIM",
+                        dkim_name,
+                        "published key does not match the domain's key".to_string(),
+                    ),
+                    None => Check::ok(
+                        "DKIM",
+                        dkim_name,
+                        "public key unavailable, record presence only".to_string(),
+                    ),
+                }
+            }
+            Err(err) => Check::error("DKIM", dkim_name, err),
+        });
+    } else {
+        checks.push(Check {
+            record: "DKIM",
+            name: format!("_domainkey.{}.", name),
+            status: "skipped",
+            details: "no DKIM selector configured for this domain".to_string(),
+        });
+    }
+
+    // DMARC and MTA-STS
+    checks.push(check_txt(
+        &resolver,
+        "DMARC",
+        format!("_dmarc.{}.", name),
+        "v=DMARC1",
+    ));
+    let mta_sts = check_txt(
+        &resolver,
+        "MTA-STS",
+        format!("_mta-sts.{}.", name),
+        "v=STSv1",
+    );
+    checks.push(
+        if mta_sts.status == "ok" && mta_sts_id(&mta_sts.details).is_none() {
+            Check::mismatch(
+                mta_sts.record,
+                mta_sts.name,
+                format!(
+                    "found '{}', expected an 'id' of 1 to 32 letters and digits",
+                    mta_sts.details
+                ),
+            )
+        } else {
+            mta_sts
+        },
+    );
+
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["Record", "Name", "Status", "Details"]
+            .iter()
+            .map(|h| Cell::new(h).with_style(Attr::Bold))
+            .collect(),
+    ));
+    for check in &checks {
+        table.add_row(Row::new(vec![
+            Cell::new(check.record),
+            Cell::new(&check.name),
+            Cell::new(check.status),
+            Cell::new(&check.details),
+        ]));
+    }
+    eprintln!();
+    table.printstd();
+    eprintln!();
+
+    let failures = checks
+        .iter()
+        .filter(|check| !matches!(check.status, "ok" | "skipped"))
+        .count();
+    if failures == 0 {
+        eprintln!("All records for domain '{}' are published correctly.", name);
+    } else {
+        eprintln!(
+            "{} record{} for domain '{}' missing or mismatched.",
+            failures,
+            if failures == 1 { " is" } else { "s are" },
+            name
+        );
+        std::process::exit(1);
+    }
+}
+
+/// Returns the DKIM key and selector stored for a domain.
+pub fn domain_dkim(domain: &Principal, name: &str) -> Option<(String, DkimKey)> {
+    let selector = domain.dkim().and_then(|dkim| dkim.selector());
+    match (selector, domain.secret()) {
+        (Some(selector), Some(secret)) => match DkimKey::from_pem(secret) {
+            Ok(dkim_key) => Some((selector.to_string(), dkim_key)),
+            Err(err) => {
+                eprintln!(
+                    "Warning: Ignoring invalid DKIM key of domain '{}': {}",
+                    name, err
+                );
+                None
+            }
+        },
+        (Some(_), None) => {
+            eprintln!(
+                "Warning: The server did not return the DKIM key of domain '{}'.",
+                name
+            );
+            None
+        }
+        _ => None,
+    }
+}
+
+pub fn domain_records(domain: &Principal, name: &str, server: &ServerAddress) -> Vec<DnsRecord> {
+    let hostname = format!("{}.", server.hostname);
+    let mut records = vec![
+        DnsRecord::new(format!("{}.", name), "MX", format!("10 {}", hostname)),
+        DnsRecord::new(format!("{}.", name), "TXT", "v=spf1 mx -all"),
+    ];
+    if let Some((selector, dkim_key)) = domain_dkim(domain, name) {
+        records.push(DnsRecord::new(
+            format!("{}._domainkey.{}.", selector, name),
+            "TXT",
+            dkim_key.dns_record_value(),
+        ));
+    }
+    records.extend([
+        DnsRecord::new(
+            format!("_dmarc.{}.", name),
+            "TXT",
+            format!(
+                "v=DMARC1; p=reject; rua=mailto:postmaster@{}; ruf=mailto:postmaster@{}",
+                name, name
+            ),
+        ),
+        DnsRecord::new(format!("autoconfig.{}.", name), "CNAME", &hostname),
+        DnsRecord::new(format!("autodiscover.{}.", name), "CNAME", &hostname),
+        DnsRecord::new(
+            format!("_jmap._tcp.{}.", name),
+            "SRV",
+            format!("0 1 {} {}", server.port, hostname),
+        ),
+        DnsRecord::new(
+            format!("_imaps._tcp.{}.", name),
+            "SRV",
+            format!("0 1 993 {}", hostname),
+        ),
+        DnsRecord::new(
+            format!("_submissions._tcp.{}.", name),
+            "SRV",
+            format!("0 1 465 {}", hostname),
+        ),
+        DnsRecord::new(
+            format!("_submission._tcp.{}.", name),
+            "SRV",
+            format!("0 1 587 {}", hostname),
+        ),
+        DnsRecord::new(format!("mta-sts.{}.", name), "CNAME", &hostname),
+        DnsRecord::new(
+            format!("_mta-sts.{}.", name),
+            "TXT",
+            mta_sts_record(&server.hostname),
+        ),
+        DnsRecord::new(
+            format!("_smtp._tls.{}.", name),
+            "TXT",
+            format!("v=TLSRPTv1; rua=mailto:postmaster@{}", name),
+        ),
+    ]);
+    records
+}
+
+/// Returns the MTA-STS TXT record value, its id is derived from the policy so
+/// that it only changes when the policy does.
+fn mta_sts_record(hostname: &str) -> String {
+    let policy = format!(
+        "version: STSv1\nmode: enforce\nmx: {}\nmax_age: 604800\n",
+        hostname
+    );
+    format!(
+        "v=STSv1; id={}",
+        digest(&SHA256, policy.as_bytes())
+            .as_ref()
+            .iter()
+            .take(10)
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>()
+    )
+}
+
+/// Returns the policy id of an MTA-STS record if it is well formed. As per
+/// RFC 8461 the id is opaque, so any value of 1 to 32 alphanumerics is valid.
+fn mta_sts_id(record: &str) -> Option<&str> {
+    record
+        .split(';')
+        .filter_map(|field| field.trim().split_once('='))
+        .find(|(key, _)| key.trim() == "id")
+        .map(|(_, id)| id.trim())
+        .filter(|id| {
+            (1..=32).contains(&id.len()) && id.chars().all(|ch| ch.is_ascii_alphanumeric())
+        })
+}
+
+struct Check {
+    record: &'static str,
+    name: String,
+    status: &'static str,
+    details: String,
+}
+
+impl Check {
+    fn ok(record: &'static str, name: String, details: String) -> Self {
+        Check {
+            record,
+            name,
+            status: "ok",
+            details,
+        }
+    }
+
+    fn missing(record: &'static str, name: String, details: String) -> Self {
+        Check {
+            record,
+            name,
+            status: "missing",
+            details,
+        }
+    }
+
+    fn mismatch(record: &'static str, name: String, details: String) -> Self {
+        Check {
+            record,
+            name,
+            status: "mismatch",
+            details,
+        }
+    }
+
+    fn error(record: &'static str, name: String, details: String) -> Self {
+        Check {
+            record,
+            name,
+            status: "error",
+            details,
+        }
+    }
+}
+
+fn check_txt(resolver: &Resolver, record: &'static str, name: String, prefix: &str) -> Check {
+    match txt_records(resolver, &name) {
+        Ok(records) => {
+            let records = records
+                .into_iter()
+                .filter(|value| {
+                    value
+                        .get(..prefix.len())
+                        .map_or(false, |value| value.eq_ignore_ascii_case(prefix))
+                })
+                .collect::<Vec<_>>();
+            match records.len() {
+                0 => Check::missing(record, name, format!("no '{}' record found", prefix)),
+                1 => Check::ok(record, name, records.into_iter().next().unwrap()),
+                _ => Check::mismatch(
+                    record,
+                    name,
+                    format!("{} '{}' records found, expected one", records.len(), prefix),
+                ),
+            }
+        }
+        Err(err) => Check::error(record, name, err),
+    }
+}
+
+fn build_resolver(address: Option<&str>) -> Resolver {
+    if let Some(address) = address {
+        let address = address
+            .parse::<SocketAddr>()
+            .or_else(|_| address.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+            .unwrap_or_else(|_| {
+                eprintln!("Error: Invalid resolver address '{}'.", address);
+                std::process::exit(1);
+            });
+        Resolver::new(
+            ResolverConfig::from_parts(
+                None,
+                vec![],
+                NameServerConfigGroup::from_ips_clear(&[address.ip()], address.port(), true),
+            ),
+            ResolverOpts::default(),
+        )
+    } else {
+        Resolver::from_system_conf()
+    }
+    .unwrap_result("create DNS resolver")
+}
+
+fn txt_records(resolver: &Resolver, name: &str) -> Result<Vec<String>, String> {
+    match resolver.txt_lookup(name) {
+        Ok(lookup) => Ok(lookup
+            .iter()
+            .map(|txt| {
+                txt.txt_data()
+                    .iter()
+                    .map(|data| String::from_utf8_lossy(data))
+                    .collect::<String>()
+            })
+            .collect()),
+        Err(err) => match err.kind() {
+            ResolveErrorKind::NoRecordsFound { .. } => Ok(vec![]),
+            _ => Err(err.to_string()),
+        },
+    }
+}
+
+fn mx_records(resolver: &Resolver, name: &str) -> Result<Vec<String>, String> {
+    match resolver.mx_lookup(name) {
+        Ok(lookup) => Ok(lookup
+            .iter()
+            .map(|mx| {
+                mx.exchange()
+                    .to_ascii()
+                    .trim_end_matches('.')
+                    .to_ascii_lowercase()
+            })
+            .collect()),
+        Err(err) => match err.kind() {
+            ResolveErrorKind::NoRecordsFound { .. } => Ok(vec![]),
+            _ => Err(err.to_string()),
+        },
+    }
+}
+
+fn dkim_public_key(record: &str) -> Option<String> {
+    record.split(';').find_map(|tag| {
+        let (name, value) = tag.split_once('=')?;
+        if name.trim() == "p" {
+            Some(value.split_whitespace().collect())
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mta_sts_id, mta_sts_record};
+
+    #[test]
+    fn parse_mta_sts_ids() {
+        for (record, id) in [
+            ("v=STSv1; id=20160831085700Z", Some("20160831085700Z")),
+            ("v=STSv1;id=abc123;", Some("abc123")),
+            ("v=STSv1; id = 1 ; ext=value", Some("1")),
+            ("v=STSv1", None),
+            ("v=STSv1; id=", None),
+            ("v=STSv1; id=2016-08-31", None),
+            ("v=STSv1; id=000000000000000000000000000000000", None),
+        ] {
+            assert_eq!(mta_sts_id(record), id, "{}", record);
+        }
+        assert!(mta_sts_id(&mta_sts_record("mail.example.com")).is_some());
+    }
+}
@@ -21,43 +21,70 @@
  * for more details.
 */
 
-use std::fs;
+use std::path::PathBuf;
 
 use jmap_client::{
     client::Client,
     core::{query::Filter, set::SetObject},
-    principal::{query, Property, Type, DKIM},
+    principal::{query, Principal, Property, Type, DKIM},
 };
 
-use crate::modules::UnwrapResult;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{Attr, Cell, Row, Table};
+
+use crate::modules::{confirm, UnwrapResult};
 
 use super::{
-    cli::DomainCommands,
-    common::{display_principal, list_principals},
+    address::{display_domain, normalize_domain},
+    alias_domain::cmd_alias_domain,
+    cli::{
+        CatchAllCommands, DkimAlgorithm, DnsFormat, DomainCommands, DomainDefaultsCommands,
+        OutputFormat,
+    },
+    common::{
+        display_principal_with, fetch_addressable_principals, fetch_principals, has_alias,
+        list_principals, principal_to_id, principals_in_domain, remove_from_memberships,
+        text_filter, type_name,
+    },
+    config::Config,
+    dkim::{cmd_dkim, default_selector, DkimKey, DkimKeyType, RECOMMENDED_RSA_BITS},
+    dns::{cmd_dns, cmd_verify},
+    template::{display_defaults, update_defaults},
+    timezone::validate_timezone,
+    usage::{format_size, principals_usage, print_usage, USAGE_PROPERTIES},
 };
 
-pub fn cmd_domain(client: Client, command: DomainCommands) {
+pub fn cmd_domain(client: Client, command: DomainCommands, url: &str) {
     match command {
         DomainCommands::Create {
             name,
             description,
             cert_dkim,
+            generate_dkim,
             selector_dkim,
             expiration_dkim,
+            timezone,
         } => {
+            let timezone = validate_timezone(timezone);
             let mut request = client.build();
             let create_request = request.set_principal().create();
             create_request.ptype(Type::Domain).name(&name);
             if description.is_some() {
                 create_request.description(description);
             }
-            if let Some(cert_dkim) = cert_dkim {
-                create_request.secret(
-                    fs::read_to_string(cert_dkim).unwrap_result("read DKIM certificate file."),
-                );
+            if timezone.is_some() {
+                create_request.timezone(timezone);
+            }
+            let dkim_key = load_dkim_key(cert_dkim, generate_dkim);
+            let selector_dkim = selector_dkim.or_else(|| generate_dkim.map(|_| default_selector()));
+            if let Some(dkim_key) = &dkim_key {
+                create_request.secret(dkim_key.private_key.clone());
             }
             if selector_dkim.is_some() || expiration_dkim.is_some() {
-                create_request.dkim(DKIM::new(selector_dkim, expiration_dkim.map(|s| s as i64)));
+                create_request.dkim(DKIM::new(
+                    selector_dkim.clone(),
+                    expiration_dkim.map(|s| s as i64),
+                ));
             }
             let create_id = create_request.create_id().unwrap();
             request
@@ -65,50 +92,352 @@ pub fn cmd_domain(client: Client, command: DomainCommands) {
                 .unwrap_result("create domain")
                 .created(&create_id)
                 .unwrap_result("create domain");
-            eprintln!("Domain '{}' successfully created.", name);
+            if let Some(dkim_key) = dkim_key {
+                eprintln!(
+                    "Domain '{}' successfully created with a {} DKIM key.",
+                    name,
+                    dkim_key.description()
+                );
+                if generate_dkim.is_some() {
+                    print_dkim_record(&dkim_key, selector_dkim.as_deref().unwrap(), &name);
+                }
+            } else {
+                eprintln!("Domain '{}' successfully created.", name);
+            }
         }
         DomainCommands::Update {
             name,
             description,
             cert_dkim,
+            generate_dkim,
             selector_dkim,
             expiration_dkim,
+            timezone,
         } => {
+            let timezone = validate_timezone(timezone);
             let update_id = domain_to_id(&client, &name);
             let mut request = client.build();
             let update_request = request.set_principal().update(&update_id);
             if description.is_some() {
                 update_request.description(description);
             }
-            if let Some(cert_dkim) = cert_dkim {
-                update_request.secret(
-                    fs::read_to_string(cert_dkim).unwrap_result("read DKIM certificate file."),
-                );
+            if timezone.is_some() {
+                update_request.timezone(timezone);
+            }
+            let dkim_key = load_dkim_key(cert_dkim, generate_dkim);
+            let selector_dkim = selector_dkim.or_else(|| generate_dkim.map(|_| default_selector()));
+            if let Some(dkim_key) = &dkim_key {
+                update_request.secret(dkim_key.private_key.clone());
             }
             if selector_dkim.is_some() || expiration_dkim.is_some() {
-                update_request.dkim(DKIM::new(selector_dkim, expiration_dkim.map(|s| s as i64)));
+                update_request.dkim(DKIM::new(
+                    selector_dkim.clone(),
+                    expiration_dkim.map(|s| s as i64),
+                ));
             }
             request
                 .send_set_principal()
                 .unwrap_result("update domain")
                 .updated(&update_id)
                 .unwrap_result("update domain");
-            eprintln!("Domain '{}' successfully updated.", name);
+            if let Some(dkim_key) = dkim_key {
+                eprintln!(
+                    "Domain '{}' successfully updated with a {} DKIM key.",
+                    name,
+                    dkim_key.description()
+                );
+                if generate_dkim.is_some() {
+                    print_dkim_record(&dkim_key, selector_dkim.as_deref().unwrap(), &name);
+                }
+            } else {
+                eprintln!("Domain '{}' successfully updated.", name);
+            }
         }
-        DomainCommands::Delete { name } => {
+        DomainCommands::Delete {
+            name,
+            cascade,
+            reassign_to,
+            force,
+        } => {
+            let name = name.to_ascii_lowercase();
+            let domain_id = domain_to_id(&client, &name);
+            let dependents = domain_dependents(&client, &name);
+
+            if !dependents.is_empty() {
+                eprintln!("Domain '{}' is in use by the following principals:\n", name);
+                print_dependents(&dependents);
+
+                if let Some(reassign_to) = reassign_to {
+                    let reassign_to = reassign_to.to_ascii_lowercase();
+                    if reassign_to == name {
+                        eprintln!("Error: Cannot reassign principals to the domain being deleted.");
+                        std::process::exit(1);
+                    }
+                    domain_to_id(&client, &reassign_to);
+                    if !force
+                        && !confirm(&format!(
+                            "Addresses in domain '{}' will be moved to domain '{}'. Continue?",
+                            name, reassign_to
+                        ))
+                    {
+                        eprintln!("Operation cancelled.");
+                        return;
+                    }
+                    if move_addresses(&client, &dependents, &name, &reassign_to, false) > 0 {
+                        eprintln!("\nDomain '{}' was not deleted.", name);
+                        std::process::exit(1);
+                    }
+                } else if cascade {
+                    if !force
+                        && !confirm(&format!(
+                            "Principals in domain '{}' will be permanently deleted and their aliases removed. Continue?",
+                            name
+                        ))
+                    {
+                        eprintln!("Operation cancelled.");
+                        return;
+                    }
+                    detach_dependents(&client, &dependents);
+                } else {
+                    eprintln!(
+                        "\nError: Domain '{}' is still in use, use --cascade to delete its principals or --reassign-to to move them to another domain.",
+                        name
+                    );
+                    std::process::exit(1);
+                }
+            }
+
             client
-                .principal_destroy(&domain_to_id(&client, &name))
+                .principal_destroy(&domain_id)
                 .unwrap_result("delete Domain");
+
+            let mut config = Config::load();
+            let has_defaults = config.domains.remove(&name).is_some();
+            let has_dkim_rotation = config.dkim.remove(&name).is_some();
+            let num_alias_domains = config.alias_domains.len();
+            config.alias_domains.retain(|alias_domain, target_domain| {
+                alias_domain != &name && target_domain != &name
+            });
+            if has_defaults || has_dkim_rotation || num_alias_domains != config.alias_domains.len()
+            {
+                config.save();
+            }
             eprintln!("Domain '{}' successfully deleted.", name);
         }
+        DomainCommands::Rename {
+            name,
+            new_name,
+            keep_aliases,
+            force,
+        } => {
+            let name = name.to_ascii_lowercase();
+            let new_name = new_name.to_ascii_lowercase();
+            if name == new_name {
+                eprintln!("Error: The new domain name is the same as the current one.");
+                std::process::exit(1);
+            }
+            let domain_id = domain_to_id(&client, &name);
+            let domain = client
+                .principal_get(
+                    &domain_id,
+                    [
+                        Property::Name,
+                        Property::Description,
+                        Property::Timezone,
+                        Property::DKIM,
+                        Property::Secret,
+                    ]
+                    .into(),
+                )
+                .unwrap_result("fetch domain")
+                .unwrap_result("find domain");
+            let dependents = domain_dependents(&client, &name);
+
+            // Servers that do not return the private key would leave the new
+            // domain with a DKIM selector but no key to sign with.
+            let copy_dkim = domain.secret().is_some()
+                || domain.dkim().and_then(|dkim| dkim.selector()).is_none();
+            if !copy_dkim {
+                eprintln!(
+                    "Warning: The server did not return the DKIM private key of domain '{}', set up a new key for '{}' with 'domain update {} --generate-dkim' after the rename.",
+                    name, new_name, new_name
+                );
+            }
+
+            if !force
+                && !confirm(&format!(
+                    "The addresses of {} principals will be moved from domain '{}' to '{}'. Continue?",
+                    dependents.len(),
+                    name,
+                    new_name
+                ))
+            {
+                eprintln!("Operation cancelled.");
+                return;
+            }
+
+            // Create the new domain with the same settings, or reuse it when
+            // resuming a previous rename.
+            if domain_exists(&client, &new_name) {
+                eprintln!(
+                    "Domain '{}' already exists, moving addresses into it.",
+                    new_name
+                );
+            } else {
+                let mut request = client.build();
+                let create_request = request.set_principal().create();
+                create_request.ptype(Type::Domain).name(&new_name);
+                if let Some(description) = domain.description() {
+                    create_request.description(Some(description.to_string()));
+                }
+                if let Some(timezone) = domain.timezone() {
+                    create_request.timezone(Some(timezone.to_string()));
+                }
+                if let Some(secret) = domain.secret() {
+                    create_request.secret(secret.to_string());
+                }
+                if let Some(dkim) = domain.dkim().filter(|_| copy_dkim) {
+                    create_request.dkim(DKIM::new(
+                        dkim.selector().map(|selector| selector.to_string()),
+                        dkim.expiration(),
+                    ));
+                }
+                let create_id = create_request.create_id().unwrap();
+                request
+                    .send_set_principal()
+                    .unwrap_result("create domain")
+                    .created(&create_id)
+                    .unwrap_result("create domain");
+                eprintln!("Domain '{}' successfully created.", new_name);
+            }
+
+            let failures = if !dependents.is_empty() {
+                move_addresses(&client, &dependents, &name, &new_name, keep_aliases)
+            } else {
+                0
+            };
+
+            // Carry over the local settings of the domain
+            let mut config = Config::load();
+            if let Some(defaults) = config.domains.remove(&name) {
+                config.domains.insert(new_name.clone(), defaults);
+            }
+            if let Some(rotation) = config.dkim.remove(&name) {
+                config.dkim.insert(new_name.clone(), rotation);
+            }
+            if let Some(target_domain) = config.alias_domains.remove(&name) {
+                config.alias_domains.insert(new_name.clone(), target_domain);
+            }
+            for target_domain in config.alias_domains.values_mut() {
+                if target_domain == &name {
+                    *target_domain = new_name.clone();
+                }
+            }
+            config.save();
+
+            if failures > 0 {
+                eprintln!(
+                    "\nDomain '{}' was kept, run the command again to retry.",
+                    name
+                );
+                std::process::exit(1);
+            } else if keep_aliases {
+                eprintln!(
+                    "Domain '{}' was kept to receive messages for the old addresses.",
+                    name
+                );
+            } else {
+                client
+                    .principal_destroy(&domain_id)
+                    .unwrap_result("delete Domain");
+                eprintln!("Domain '{}' successfully renamed to '{}'.", name, new_name);
+            }
+        }
         DomainCommands::Display { name } => {
-            display_principal(
+            let domain_id = domain_to_id(&client, &name);
+            let catch_all = find_catch_all(&client, &name)
+                .map(|(_, email)| email)
+                .unwrap_or_default();
+            let dkim_status = dkim_status(
+                &client
+                    .principal_get(&domain_id, [Property::Name, Property::DKIM].into())
+                    .unwrap_result("fetch domain")
+                    .unwrap_result("find domain"),
+                &Config::load(),
+            );
+            let stats = DomainStats::new(&DomainStats::fetch_principals(&client), &name);
+            display_principal_with(
                 &client,
-                &domain_to_id(&client, &name),
-                &[Property::Name, Property::Description, Property::DKIM],
+                &domain_id,
+                &[
+                    Property::Name,
+                    Property::Description,
+                    Property::Timezone,
+                    Property::DKIM,
+                    Property::ACL,
+                ],
+                &[
+                    ("Catch-all", catch_all),
+                    ("DKIM status", dkim_status),
+                    ("Accounts", stats.accounts.to_string()),
+                    ("Groups", stats.groups.to_string()),
+                    ("Lists", stats.lists.to_string()),
+                    ("Aliases", stats.aliases.to_string()),
+                    ("Total quota", format_size(stats.quota)),
+                ],
+            );
+        }
+        DomainCommands::CatchAll(CatchAllCommands::Set { name, email }) => {
+            domain_to_id(&client, &name);
+            let catch_all = format!("@{}", name.to_ascii_lowercase());
+            let update_id = principal_to_id(&client, &email);
+            let mut request = client.build();
+            let set_request = request.set_principal();
+            let previous_id = find_catch_all(&client, &name).map(|(id, _)| id);
+            if let Some(previous_id) = &previous_id {
+                if previous_id != &update_id {
+                    set_request.update(previous_id).alias(&catch_all, false);
+                }
+            }
+            set_request.update(&update_id).alias(&catch_all, true);
+            let mut response = request
+                .send_set_principal()
+                .unwrap_result("update catch-all address");
+            if let Some(previous_id) = previous_id.filter(|id| id != &update_id) {
+                response
+                    .updated(&previous_id)
+                    .unwrap_result("remove previous catch-all address");
+            }
+            response
+                .updated(&update_id)
+                .unwrap_result("update catch-all address");
+            eprintln!(
+                "Messages for unknown addresses in domain '{}' will be delivered to '{}'.",
+                name, email
             );
         }
-        DomainCommands::List { filter } => {
+        DomainCommands::CatchAll(CatchAllCommands::Clear { name }) => {
+            domain_to_id(&client, &name);
+            if let Some((update_id, _)) = find_catch_all(&client, &name) {
+                let mut request = client.build();
+                request
+                    .set_principal()
+                    .update(&update_id)
+                    .alias(&format!("@{}", name.to_ascii_lowercase()), false);
+                request
+                    .send_set_principal()
+                    .unwrap_result("remove catch-all address")
+                    .updated(&update_id)
+                    .unwrap_result("remove catch-all address");
+                eprintln!("Catch-all address of domain '{}' removed.", name);
+            } else {
+                eprintln!("Domain '{}' has no catch-all address.", name);
+            }
+        }
+        DomainCommands::List {
+            filter,
+            stats: false,
+        } => {
             list_principals(
                 &client,
                 Type::Domain,
@@ -116,15 +445,187 @@ pub fn cmd_domain(client: Client, command: DomainCommands) {
                 &[Property::Name, Property::Description],
             );
         }
+        DomainCommands::List {
+            filter,
+            stats: true,
+        } => {
+            let domains = fetch_principals(
+                &client,
+                text_filter(Type::Domain, filter),
+                &[Property::Name, Property::Description, Property::DKIM],
+            );
+            let principals = DomainStats::fetch_principals(&client);
+            let config = Config::load();
+
+            if !domains.is_empty() {
+                let mut table = Table::new();
+                table.add_row(Row::new(
+                    [
+                        "Name",
+                        "Description",
+                        "Accounts",
+                        "Groups",
+                        "Lists",
+                        "Aliases",
+                        "Total quota",
+                        "DKIM status",
+                    ]
+                    .iter()
+                    .map(|h| Cell::new(h).with_style(Attr::Bold))
+                    .collect(),
+                ));
+                for domain in &domains {
+                    let name = domain.name().unwrap_or("");
+                    let stats = DomainStats::new(&principals, name);
+                    table.add_row(Row::new(vec![
+                        Cell::new(&display_domain(name)),
+                        Cell::new(domain.description().unwrap_or("")),
+                        Cell::new(&stats.accounts.to_string()),
+                        Cell::new(&stats.groups.to_string()),
+                        Cell::new(&stats.lists.to_string()),
+                        Cell::new(&stats.aliases.to_string()),
+                        Cell::new(&format_size(stats.quota)),
+                        Cell::new(&dkim_status(domain, &config)),
+                    ]));
+                }
+                eprintln!();
+                table.printstd();
+            }
+
+            eprintln!(
+                "\n\n{} record{} found.\n",
+                domains.len(),
+                if domains.len() == 1 { "" } else { "s" }
+            );
+        }
+        DomainCommands::Defaults(DomainDefaultsCommands::Set { name, mut defaults }) => {
+            let domain_id = domain_to_id(&client, &name);
+
+            // The default timezone is stored on the domain itself
+            if let Some(timezone) = validate_timezone(defaults.timezone.take()) {
+                let mut request = client.build();
+                request
+                    .set_principal()
+                    .update(&domain_id)
+                    .timezone(Some(timezone));
+                request
+                    .send_set_principal()
+                    .unwrap_result("update domain")
+                    .updated(&domain_id)
+                    .unwrap_result("update domain");
+            }
+
+            let mut config = Config::load();
+            let domain_defaults = config.domains.entry(name.to_lowercase()).or_default();
+            update_defaults(domain_defaults, defaults);
+            if domain_defaults.is_empty() {
+                config.domains.remove(&name.to_lowercase());
+            }
+            config.save();
+            eprintln!("Default settings for domain '{}' successfully saved.", name);
+        }
+        DomainCommands::Defaults(DomainDefaultsCommands::Clear { name }) => {
+            let mut config = Config::load();
+            if config.domains.remove(&name.to_lowercase()).is_some() {
+                config.save();
+            }
+            eprintln!(
+                "Default settings for domain '{}' successfully removed.",
+                name
+            );
+        }
+        DomainCommands::Defaults(DomainDefaultsCommands::Display { name }) => {
+            let mut defaults = Config::load()
+                .domains
+                .get(&name.to_lowercase())
+                .cloned()
+                .unwrap_or_default();
+            defaults.timezone = domain_timezone(&client, &name);
+            display_defaults(&defaults);
+        }
+        DomainCommands::Usage { name, format } => {
+            // Make sure the domain exists
+            domain_to_id(&client, &name);
+            let principals =
+                principals_in_domain(&client, Type::Individual, &name, &USAGE_PROPERTIES);
+            print_usage(
+                &principals_usage(&client, &principals),
+                format.unwrap_or(OutputFormat::Table),
+            );
+        }
+        DomainCommands::Alias(command) => {
+            cmd_alias_domain(client, command);
+        }
+        DomainCommands::Dkim(command) => {
+            cmd_dkim(client, command);
+        }
+        DomainCommands::Verify { name, resolver } => {
+            cmd_verify(&client, url, &name, resolver);
+        }
+        DomainCommands::Dns { name, format } => {
+            cmd_dns(&client, url, &name, format.unwrap_or(DnsFormat::Bind));
+        }
     }
 }
 
+pub fn find_catch_all(client: &Client, name: &str) -> Option<(String, String)> {
+    let catch_all = format!("@{}", name);
+    fetch_addressable_principals(client, &[Property::Id, Property::Email, Property::Aliases])
+        .into_iter()
+        .find(|principal| has_alias(principal, &catch_all))
+        .map(|mut principal| {
+            (
+                principal.take_id(),
+                principal.email().unwrap_or("").to_string(),
+            )
+        })
+}
+
+pub fn domain_timezone(client: &Client, name: &str) -> Option<String> {
+    let id = client
+        .principal_query(
+            Filter::and([
+                query::Filter::ptype(Type::Domain),
+                query::Filter::domain_name(name),
+            ])
+            .into(),
+            None::<Vec<_>>,
+        )
+        .unwrap_result("query principals")
+        .take_ids()
+        .pop()?;
+    client
+        .principal_get(&id, [Property::Timezone].into())
+        .unwrap_result("fetch principal")?
+        .timezone()
+        .map(|timezone| timezone.to_string())
+}
+
+pub fn domain_exists(client: &Client, name: &str) -> bool {
+    !client
+        .principal_query(
+            Filter::and([
+                query::Filter::ptype(Type::Domain),
+                query::Filter::domain_name(name),
+            ])
+            .into(),
+            None::<Vec<_>>,
+        )
+        .unwrap_result("query principals")
+        .ids()
+        .is_empty()
+}
+
 pub fn domain_to_id(client: &Client, name: &str) -> String {
+    let name = normalize_domain(name).unwrap_or_else(|err| {
+        eprintln!("Error: Domain '{}' {}.", name, err);
+        std::process::exit(1);
+    });
     let mut response = client
         .principal_query(
             Filter::and([
                 query::Filter::ptype(Type::Domain),
-                query::Filter::domain_name(name),
+                query::Filter::domain_name(&name),
             ])
             .into(),
             None::<Vec<_>>,
@@ -142,3 +643,337 @@ pub fn domain_to_id(client: &Client, name: &str) -> String {
         }
     }
 }
+
+fn load_dkim_key(
+    cert_dkim: Option<PathBuf>,
+    generate_dkim: Option<DkimAlgorithm>,
+) -> Option<DkimKey> {
+    if let Some(cert_dkim) = cert_dkim {
+        let dkim_key = DkimKey::from_file(&cert_dkim).unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        });
+        if dkim_key.key_type == DkimKeyType::Rsa && dkim_key.bits < RECOMMENDED_RSA_BITS {
+            eprintln!(
+                "Warning: DKIM key is {} bits, RFC 8301 recommends at least {} bits.",
+                dkim_key.bits, RECOMMENDED_RSA_BITS
+            );
+        }
+        Some(dkim_key)
+    } else {
+        generate_dkim.map(DkimKey::generate)
+    }
+}
+
+fn print_dkim_record(dkim_key: &DkimKey, selector: &str, name: &str) {
+    eprintln!("\nPublish the following DNS record:\n");
+    println!(
+        "{}",
+        dkim_key.dns_txt_record(selector, &name.to_ascii_lowercase())
+    );
+}
+
+struct DomainStats {
+    accounts: usize,
+    groups: usize,
+    lists: usize,
+    aliases: usize,
+    quota: u64,
+}
+
+impl DomainStats {
+    /// Fetches the principals needed to compute the statistics of any domain.
+    fn fetch_principals(client: &Client) -> Vec<Principal> {
+        fetch_addressable_principals(
+            client,
+            &[
+                Property::Type,
+                Property::Email,
+                Property::Aliases,
+                Property::Quota,
+            ],
+        )
+    }
+
+    /// Aliases are counted over all principals, as alias domain addresses and
+    /// catch-alls may belong to principals whose primary address is elsewhere.
+    fn new(principals: &[Principal], name: &str) -> Self {
+        let suffix = format!("@{}", name.to_ascii_lowercase());
+        let in_domain = |address: &str| address.to_ascii_lowercase().ends_with(&suffix);
+        let mut stats = DomainStats {
+            accounts: 0,
+            groups: 0,
+            lists: 0,
+            aliases: 0,
+            quota: 0,
+        };
+
+        for principal in principals {
+            stats.aliases += principal
+                .aliases()
+                .into_iter()
+                .flatten()
+                .filter(|alias| in_domain(alias))
+                .count();
+            if !principal.email().map_or(false, in_domain) {
+                continue;
+            }
+            match principal.ptype() {
+                Some(Type::Individual) => {
+                    stats.accounts += 1;
+                    stats.quota += principal.quota().unwrap_or(0) as u64;
+                }
+                Some(Type::Group) => stats.groups += 1,
+                Some(Type::List) => stats.lists += 1,
+                _ => (),
+            }
+        }
+
+        stats
+    }
+}
+
+fn dkim_status(domain: &Principal, config: &Config) -> String {
+    let mut status = domain
+        .dkim()
+        .and_then(|dkim| dkim.selector())
+        .map(|selector| format!("signing with '{}'", selector))
+        .unwrap_or_else(|| "not configured".to_string());
+    if let Some(pending) = config
+        .dkim
+        .get(&domain.name().unwrap_or("").to_ascii_lowercase())
+        .and_then(|rotation| rotation.pending.as_ref())
+    {
+        status.push_str(&format!(", '{}' pending activation", pending.selector));
+    }
+    status
+}
+
+struct Dependent {
+    id: String,
+    email: String,
+    ptype: &'static str,
+    primary: bool,
+    aliases: Vec<String>,
+}
+
+/// Returns the principals with a primary address or aliases in a domain.
+fn domain_dependents(client: &Client, name: &str) -> Vec<Dependent> {
+    let suffix = format!("@{}", name);
+    let in_domain = |address: &str| address.to_ascii_lowercase().ends_with(&suffix);
+
+    fetch_addressable_principals(
+        client,
+        &[
+            Property::Id,
+            Property::Type,
+            Property::Email,
+            Property::Aliases,
+        ],
+    )
+    .into_iter()
+    .filter_map(|principal| {
+        let email = principal.email().unwrap_or("").to_string();
+        let primary = in_domain(&email);
+        let aliases = principal
+            .aliases()
+            .map(|aliases| {
+                aliases
+                    .iter()
+                    .filter(|alias| in_domain(alias))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        if primary || !aliases.is_empty() {
+            Some(Dependent {
+                id: principal.id().unwrap_or("").to_string(),
+                ptype: principal.ptype().map(type_name).unwrap_or(""),
+                email,
+                primary,
+                aliases,
+            })
+        } else {
+            None
+        }
+    })
+    .collect()
+}
+
+fn print_dependents(dependents: &[Dependent]) {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["Principal", "Type", "Addresses in domain"]
+            .iter()
+            .map(|h| Cell::new(h).with_style(Attr::Bold))
+            .collect(),
+    ));
+    for dependent in dependents {
+        let mut addresses = dependent.aliases.clone();
+        if dependent.primary {
+            addresses.insert(0, dependent.email.clone());
+        }
+        table.add_row(Row::new(vec![
+            Cell::new(&dependent.email),
+            Cell::new(dependent.ptype),
+            Cell::new(&addresses.join("\n")),
+        ]));
+    }
+    table.printstd();
+}
+
+/// Deletes the principals whose primary address is in the domain and
+/// removes the domain's aliases from all others.
+fn detach_dependents(client: &Client, dependents: &[Dependent]) {
+    let mut request = client.build();
+    let set_request = request.set_principal();
+    for dependent in dependents.iter().filter(|dependent| !dependent.primary) {
+        let update_request = set_request.update(&dependent.id);
+        for alias in &dependent.aliases {
+            update_request.alias(alias, false);
+        }
+    }
+    let mut failures = 0;
+    if dependents.iter().any(|dependent| !dependent.primary) {
+        let mut response = request.send_set_principal().unwrap_result("remove aliases");
+        for dependent in dependents.iter().filter(|dependent| !dependent.primary) {
+            if let Err(err) = response.updated(&dependent.id) {
+                eprintln!(
+                    "Failed to remove aliases from '{}': {}",
+                    dependent.email, err
+                );
+                failures += 1;
+            }
+        }
+    }
+
+    for dependent in dependents.iter().filter(|dependent| dependent.primary) {
+        remove_from_memberships(client, &dependent.id);
+        if let Err(err) = client.principal_destroy(&dependent.id) {
+            eprintln!("Failed to delete '{}': {}", dependent.email, err);
+            failures += 1;
+        } else {
+            eprintln!("Deleted {} '{}'.", dependent.ptype, dependent.email);
+        }
+    }
+
+    if failures > 0 {
+        eprintln!(
+            "\nThere were {} failures, the domain was not deleted.",
+            failures
+        );
+        std::process::exit(1);
+    }
+}
+
+/// Moves the primary addresses and aliases of the dependents from one
+/// domain to another, refusing to proceed if any new address is taken.
+/// Returns the number of principals that could not be updated.
+fn move_addresses(
+    client: &Client,
+    dependents: &[Dependent],
+    from: &str,
+    to: &str,
+    keep_old: bool,
+) -> usize {
+    let rename = |address: &str| format!("{}@{}", &address[..address.len() - from.len() - 1], to);
+
+    // Make sure none of the new addresses is already in use
+    let ids = dependents
+        .iter()
+        .map(|dependent| dependent.id.as_str())
+        .collect::<Vec<_>>();
+    let new_addresses = dependents
+        .iter()
+        .flat_map(|dependent| {
+            dependent
+                .aliases
+                .iter()
+                .map(|alias| rename(alias))
+                .chain(dependent.primary.then(|| rename(&dependent.email)))
+        })
+        .collect::<Vec<_>>();
+    let conflicts =
+        fetch_addressable_principals(client, &[Property::Id, Property::Email, Property::Aliases])
+            .into_iter()
+            .filter(|principal| !ids.contains(&principal.id().unwrap_or("")))
+            .flat_map(|principal| {
+                principal
+                    .email()
+                    .map(|email| email.to_string())
+                    .into_iter()
+                    .chain(principal.aliases().into_iter().flatten().cloned())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|address| {
+                new_addresses
+                    .iter()
+                    .any(|new_address| new_address.eq_ignore_ascii_case(address))
+            })
+            .collect::<Vec<_>>();
+    if !conflicts.is_empty() {
+        eprintln!(
+            "Error: The following addresses are already in use in domain '{}': {}.",
+            to,
+            conflicts.join(", ")
+        );
+        std::process::exit(1);
+    }
+
+    let chunk_size = client
+        .session()
+        .core_capabilities()
+        .map(|c| c.max_objects_in_set())
+        .unwrap_or(100)
+        .max(1);
+    let pb = ProgressBar::new(dependents.len() as u64);
+    pb.set_style(
+        ProgressStyle::with_template("{prefix:.bold.dim} [{bar:40}] {pos}/{len} {wide_msg}")
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    pb.set_prefix("Moving");
+
+    let mut failures = Vec::new();
+    for chunk in dependents.chunks(chunk_size) {
+        let mut request = client.build();
+        let set_request = request.set_principal();
+        for dependent in chunk {
+            let update_request = set_request.update(&dependent.id);
+            if dependent.primary {
+                update_request.email(rename(&dependent.email));
+                if keep_old {
+                    update_request.alias(&dependent.email, true);
+                }
+            }
+            for alias in &dependent.aliases {
+                if !keep_old {
+                    update_request.alias(alias, false);
+                }
+                update_request.alias(&rename(alias), true);
+            }
+        }
+        let mut response = request.send_set_principal().unwrap_result("move addresses");
+        for dependent in chunk {
+            if let Err(err) = response.updated(&dependent.id) {
+                failures.push(format!("Failed to move '{}': {}", dependent.email, err));
+            }
+            pb.inc(1);
+        }
+    }
+    pb.finish_with_message("Done");
+
+    eprintln!(
+        "\nMoved the addresses of {} of {} principals to domain '{}'.",
+        dependents.len() - failures.len(),
+        dependents.len(),
+        to
+    );
+    if !failures.is_empty() {
+        eprintln!("There were {} failures:\n", failures.len());
+        for failure in &failures {
+            eprintln!("{}", failure);
+        }
+    }
+    failures.len()
+}
@@ -0,0 +1,411 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Command Line Interface.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{TimeZone, Utc};
+use indicatif::{ProgressBar, ProgressStyle};
+use jmap_client::{
+    client::Client,
+    core::set::SetObject,
+    email,
+    mailbox::{self, Role},
+};
+
+use super::{cli::MailboxFormat, UnwrapResult};
+
+pub struct MailboxInfo {
+    pub id: String,
+    pub path: Vec<String>,
+    pub is_inbox: bool,
+}
+
+pub struct MessageInfo {
+    pub blob_id: String,
+    pub mailbox_ids: Vec<String>,
+    pub keywords: Vec<String>,
+    pub received_at: Option<i64>,
+}
+
+pub fn fetch_mailboxes(client: &Client, account_id: &str) -> Vec<MailboxInfo> {
+    let mut request = client.build();
+    request.get_mailbox().account_id(account_id).properties([
+        mailbox::Property::Name,
+        mailbox::Property::ParentId,
+        mailbox::Property::Role,
+        mailbox::Property::Id,
+    ]);
+    let response = request.send_get_mailbox().unwrap_result("fetch mailboxes");
+    let mailboxes = response
+        .list()
+        .iter()
+        .map(|mailbox| {
+            (
+                mailbox.id().unwrap(),
+                (
+                    mailbox.name().unwrap_or("Untitled"),
+                    mailbox.parent_id(),
+                    mailbox.role() == Role::Inbox,
+                ),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut results = mailboxes
+        .iter()
+        .map(|(id, (_, _, is_inbox))| {
+            let mut path = Vec::new();
+            let mut current_id = Some(*id);
+            while let Some((name, parent_id, _)) = current_id.and_then(|id| mailboxes.get(id)) {
+                path.push(name.to_string());
+                current_id = *parent_id;
+                if path.len() > mailboxes.len() {
+                    break;
+                }
+            }
+            path.reverse();
+
+            MailboxInfo {
+                id: id.to_string(),
+                path,
+                is_inbox: *is_inbox,
+            }
+        })
+        .collect::<Vec<_>>();
+    results.sort_by(|a, b| a.path.cmp(&b.path));
+    results
+}
+
+pub fn fetch_messages(client: &Client, account_id: &str) -> Vec<MessageInfo> {
+    let page_size = client
+        .session()
+        .core_capabilities()
+        .map(|c| c.max_objects_in_get())
+        .unwrap_or(500);
+    let mut position = 0;
+    let mut messages = Vec::new();
+
+    loop {
+        let mut request = client.build();
+        let query_ref = request
+            .query_email()
+            .account_id(account_id)
+            .position(position)
+            .limit(page_size)
+            .result_reference();
+        request
+            .get_email()
+            .account_id(account_id)
+            .ids_ref(query_ref)
+            .properties([
+                email::Property::Id,
+                email::Property::BlobId,
+                email::Property::MailboxIds,
+                email::Property::Keywords,
+                email::Property::ReceivedAt,
+            ]);
+        let emails = request
+            .send()
+            .unwrap_result("fetch emails")
+            .unwrap_method_responses()
+            .pop()
+            .unwrap_result("process response: received an empty response from server")
+            .unwrap_get_email()
+            .unwrap_result("fetch emails")
+            .take_list();
+
+        let num_emails = emails.len();
+        for email in emails {
+            if let Some(blob_id) = email.blob_id() {
+                messages.push(MessageInfo {
+                    blob_id: blob_id.to_string(),
+                    mailbox_ids: email.mailbox_ids().into_iter().map(String::from).collect(),
+                    keywords: email.keywords().into_iter().map(String::from).collect(),
+                    received_at: email.received_at(),
+                });
+            }
+        }
+
+        if num_emails < page_size {
+            break;
+        }
+        position += num_emails as i32;
+    }
+
+    messages
+}
+
+pub fn export_account(client: &mut Client, account_id: &str, path: &Path, format: MailboxFormat) {
+    let mailboxes = fetch_mailboxes(client, account_id);
+    let messages = fetch_messages(client, account_id);
+    let mut mbox_files: HashMap<&str, File> = HashMap::new();
+
+    let mailbox_paths = mailboxes
+        .iter()
+        .map(|mailbox| {
+            let mailbox_path = export_path(path, mailbox, format);
+            if format == MailboxFormat::Mbox {
+                if let Some(parent) = mailbox_path.parent() {
+                    fs::create_dir_all(parent).unwrap_result("create export directory");
+                }
+            } else {
+                for dir in ["cur", "new", "tmp"] {
+                    fs::create_dir_all(mailbox_path.join(dir))
+                        .unwrap_result("create export directory");
+                }
+            }
+            (mailbox.id.as_str(), mailbox_path)
+        })
+        .collect::<HashMap<_, _>>();
+
+    // Blobs are downloaded from the default account
+    let previous_account_id = client.default_account_id().to_string();
+    client.set_default_account_id(account_id);
+
+    let pb = ProgressBar::new(messages.len() as u64);
+    pb.set_style(
+        ProgressStyle::with_template("{prefix:.bold.dim} [{bar:40}] {pos}/{len} {wide_msg}")
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    pb.set_prefix("Exporting");
+
+    for (message_num, message) in messages.iter().enumerate() {
+        let contents = client
+            .download(&message.blob_id)
+            .unwrap_result("download message");
+
+        for mailbox_id in &message.mailbox_ids {
+            let mailbox_path = if let Some(mailbox_path) = mailbox_paths.get(mailbox_id.as_str()) {
+                mailbox_path
+            } else {
+                continue;
+            };
+
+            if format == MailboxFormat::Mbox {
+                let file = mbox_files.entry(mailbox_id.as_str()).or_insert_with(|| {
+                    OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(mailbox_path)
+                        .unwrap_result("create mbox file")
+                });
+                write_mbox_message(file, message, &contents).unwrap_result("write mbox file");
+            } else {
+                fs::write(
+                    mailbox_path.join("cur").join(format!(
+                        "{}.{}.stalwart:2,{}",
+                        message.received_at.unwrap_or(0),
+                        message_num,
+                        maildir_flags(&message.keywords)
+                    )),
+                    &contents,
+                )
+                .unwrap_result("write message");
+            }
+        }
+
+        pb.inc(1);
+    }
+
+    pb.finish_with_message("Done");
+    client.set_default_account_id(previous_account_id);
+}
+
+pub fn transfer_account(
+    client: &mut Client,
+    account_id: &str,
+    target_account_id: &str,
+    folder_name: &str,
+) -> (usize, Vec<String>) {
+    let mailboxes = fetch_mailboxes(client, account_id);
+    let messages = fetch_messages(client, account_id);
+
+    // Create the destination folder and mailbox hierarchy on the target account
+    let folder_id = fetch_mailboxes(client, target_account_id)
+        .into_iter()
+        .find(|mailbox| mailbox.path.len() == 1 && mailbox.path[0] == folder_name)
+        .map(|mailbox| mailbox.id);
+    let mut request = client.build();
+    let set_request = request.set_mailbox().account_id(target_account_id);
+    let folder_create_id = if folder_id.is_none() {
+        set_request
+            .create()
+            .name(folder_name)
+            .parent_id(None::<String>)
+            .create_id()
+    } else {
+        None
+    };
+    let mut create_ids: HashMap<&[String], String> = HashMap::new();
+    for mailbox in &mailboxes {
+        let create_request = set_request.create().name(mailbox.path.last().unwrap());
+        let parent_path = &mailbox.path[..mailbox.path.len() - 1];
+        if let Some(parent_create_id) = create_ids.get(parent_path) {
+            create_request.parent_id_ref(parent_create_id);
+        } else if let Some(folder_id) = &folder_id {
+            create_request.parent_id(folder_id.to_string().into());
+        } else {
+            create_request.parent_id_ref(folder_create_id.as_ref().unwrap());
+        }
+        create_ids.insert(mailbox.path.as_slice(), create_request.create_id().unwrap());
+    }
+    let mut response = request.send_set_mailbox().unwrap_result("create mailboxes");
+    if let Some(folder_create_id) = &folder_create_id {
+        response
+            .created(folder_create_id)
+            .unwrap_result("create mailbox");
+    }
+    let mut mailbox_ids = HashMap::with_capacity(mailboxes.len());
+    for mailbox in &mailboxes {
+        mailbox_ids.insert(
+            mailbox.id.as_str(),
+            response
+                .created(&create_ids[mailbox.path.as_slice()])
+                .unwrap_result("create mailbox")
+                .take_id(),
+        );
+    }
+
+    // Copy messages, blobs are downloaded from the default account
+    let previous_account_id = client.default_account_id().to_string();
+    client.set_default_account_id(account_id);
+
+    let pb = ProgressBar::new(messages.len() as u64);
+    pb.set_style(
+        ProgressStyle::with_template("{prefix:.bold.dim} [{bar:40}] {pos}/{len} {wide_msg}")
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    pb.set_prefix("Transferring");
+
+    let mut total_copied = 0;
+    let mut failures = Vec::new();
+    for message in &messages {
+        let result = client.download(&message.blob_id).and_then(|contents| {
+            client.email_import_account(
+                target_account_id,
+                contents,
+                message
+                    .mailbox_ids
+                    .iter()
+                    .filter_map(|id| mailbox_ids.get(id.as_str())),
+                if !message.keywords.is_empty() {
+                    message.keywords.iter().into()
+                } else {
+                    None
+                },
+                message.received_at,
+            )
+        });
+        if let Err(err) = result {
+            failures.push(format!(
+                "Failed to copy message with blob id '{}': {}",
+                message.blob_id, err
+            ));
+        } else {
+            total_copied += 1;
+        }
+        pb.inc(1);
+    }
+
+    pb.finish_with_message("Done");
+    client.set_default_account_id(previous_account_id);
+
+    (total_copied, failures)
+}
+
+fn export_path(root: &Path, mailbox: &MailboxInfo, format: MailboxFormat) -> PathBuf {
+    let names = mailbox
+        .path
+        .iter()
+        .map(|name| name.replace(|c: char| matches!(c, '/' | '\\' | '.'), "_"))
+        .collect::<Vec<_>>();
+
+    match format {
+        MailboxFormat::Mbox => root.join(format!("{}.mbox", names.join("."))),
+        _ if mailbox.is_inbox => root.to_path_buf(),
+        MailboxFormat::Maildir => root.join(format!(".{}", names.join("."))),
+        MailboxFormat::MaildirNested => names.iter().fold(root.to_path_buf(), |p, n| p.join(n)),
+    }
+}
+
+fn maildir_flags(keywords: &[String]) -> String {
+    let mut flags = keywords
+        .iter()
+        .filter_map(|keyword| match keyword.as_str() {
+            "$draft" => Some('D'),
+            "$flagged" => Some('F'),
+            "$passed" | "$forwarded" => Some('P'),
+            "$answered" => Some('R'),
+            "$seen" => Some('S'),
+            "$deleted" => Some('T'),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    flags.sort_unstable();
+    flags.dedup();
+    flags.into_iter().collect()
+}
+
+fn write_mbox_message(
+    file: &mut File,
+    message: &MessageInfo,
+    contents: &[u8],
+) -> std::io::Result<()> {
+    let date = Utc
+        .timestamp_opt(message.received_at.unwrap_or(0), 0)
+        .single()
+        .unwrap_or_else(Utc::now);
+    let mut buf = Vec::with_capacity(contents.len() + 128);
+    buf.extend_from_slice(
+        format!(
+            "From MAILER-DAEMON {}\n",
+            date.format("%a %b %e %H:%M:%S %Y")
+        )
+        .as_bytes(),
+    );
+
+    // Escape 'From ' lines using the mboxrd convention
+    for line in contents.split_inclusive(|&ch| ch == b'\n') {
+        if line
+            .iter()
+            .position(|&ch| ch != b'>')
+            .map_or(false, |pos| line[pos..].starts_with(b"From "))
+        {
+            buf.push(b'>');
+        }
+        buf.extend_from_slice(line);
+    }
+    if !buf.ends_with(b"\n") {
+        buf.push(b'\n');
+    }
+    buf.push(b'\n');
+
+    file.write_all(&buf)
+}
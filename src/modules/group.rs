@@ -31,7 +31,9 @@ use crate::modules::{common::email_to_id, UnwrapResult};
 
 use super::{
     cli::GroupCommands,
-    common::{display_principal, list_principals},
+    common::{
+        bulk_update, delete_group, display_members_principal, list_principals, update_members,
+    },
 };
 
 pub fn cmd_group(client: Client, command: GroupCommands) {
@@ -79,37 +81,11 @@ pub fn cmd_group(client: Client, command: GroupCommands) {
             eprintln!("Group '{}' successfully updated.", email);
         }
         GroupCommands::AddMembers { email, members } => {
-            let update_id = email_to_id(&client, Type::Group, &email);
-            let mut request = client.build();
-            let update_request = request.set_principal().update(&update_id);
-
-            for member in members {
-                update_request.member(&email_to_id(&client, Type::Individual, &member), true);
-            }
-
-            request
-                .send_set_principal()
-                .unwrap_result("update group")
-                .updated(&update_id)
-                .unwrap_result("update group");
-
+            update_members(&client, Type::Group, &email, &members, true, false);
             eprintln!("Group '{}' successfully updated.", email);
         }
         GroupCommands::RemoveMembers { email, members } => {
-            let update_id = email_to_id(&client, Type::Group, &email);
-            let mut request = client.build();
-            let update_request = request.set_principal().update(&update_id);
-
-            for member in members {
-                update_request.member(&email_to_id(&client, Type::Individual, &member), false);
-            }
-
-            request
-                .send_set_principal()
-                .unwrap_result("update group")
-                .updated(&update_id)
-                .unwrap_result("update group");
-
+            update_members(&client, Type::Group, &email, &members, false, false);
             eprintln!("Group '{}' successfully updated.", email);
         }
         GroupCommands::List { filter } => {
@@ -120,17 +96,26 @@ pub fn cmd_group(client: Client, command: GroupCommands) {
                 &[Property::Email, Property::Name, Property::Description],
             );
         }
-        GroupCommands::Display { email } => {
-            display_principal(
-                &client,
-                &email_to_id(&client, Type::Group, &email),
-                &[
-                    Property::Email,
-                    Property::Name,
-                    Property::Description,
-                    Property::Members,
-                ],
-            );
+        GroupCommands::Delete {
+            email,
+            force,
+            show_members,
+        } => {
+            delete_group(&client, Type::Group, &email, force, show_members);
+        }
+        GroupCommands::Display { email, expand } => {
+            display_members_principal(&client, Type::Group, &email, expand);
+        }
+        GroupCommands::BulkUpdate {
+            filter,
+            force,
+            description,
+        } => {
+            bulk_update(&client, Type::Group, filter, force, |update_request| {
+                if description.is_some() {
+                    update_request.description(description.clone());
+                }
+            });
         }
     }
 }
@@ -22,7 +22,7 @@
 */
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io::{self, BufRead, Cursor, Write},
     sync::{
         atomic::{AtomicUsize, Ordering},
@@ -47,9 +47,14 @@ use mail_parser::mailbox::{
 use crate::modules::UnwrapResult;
 
 use super::{
-    cli::{ImportCommands, MailboxFormat},
-    common::email_to_id,
+    address::{available_aliases, normalize_address, taken_addresses},
+    cli::{parse_quota, ImportCommands, MailboxFormat},
+    common::{add_to_groups, email_to_id, principal_quota},
+    config::Config,
+    domain::domain_timezone,
+    picture::Picture,
     read_file,
+    timezone::parse_timezone,
 };
 
 enum Mailbox {
@@ -81,6 +86,7 @@ pub fn cmd_import(client: Client, command: ImportCommands) {
             column_layout,
             path,
             no_domains,
+            template,
         } => {
             let mut builder = ReaderBuilder::new();
             builder.flexible(true);
@@ -137,9 +143,11 @@ pub fn cmd_import(client: Client, command: ImportCommands) {
                 for record in &records {
                     if let Some((_, domain)) = record
                         .get(email_idx)
+                        .and_then(|email| normalize_address(email).ok())
+                        .as_ref()
                         .and_then(|email| email.rsplit_once('@'))
                     {
-                        let domain = domain.to_ascii_lowercase();
+                        let domain = domain.to_string();
                         if !domains.contains(&domain) {
                             domains.push(domain);
                         }
@@ -175,6 +183,14 @@ pub fn cmd_import(client: Client, command: ImportCommands) {
             }
 
             // Bulk import accounts
+            let config = Config::load();
+            let mut taken = if !config.alias_domains.is_empty() {
+                taken_addresses(&client)
+            } else {
+                HashSet::new()
+            };
+            let mut account_groups = Vec::with_capacity(records.len());
+            let mut domain_timezones = HashMap::new();
             for (pos, record) in records.into_iter().enumerate() {
                 let mut email = None;
                 let mut name = None;
@@ -182,13 +198,13 @@ pub fn cmd_import(client: Client, command: ImportCommands) {
                 let mut description = None;
                 let mut quota = None;
                 let mut timezone = None;
+                let mut picture = None;
 
                 for (idx, hdr) in layout.iter().enumerate() {
                     if let Some(value) = record.get(idx) {
                         match hdr.as_str() {
                             "email" => {
                                 if email.is_none() {
-                                    accounts.push(value.to_string());
                                     email = value.into()
                                 }
                             }
@@ -196,33 +212,89 @@ pub fn cmd_import(client: Client, command: ImportCommands) {
                             "secret" => secret = value.into(),
                             "description" => description = value.into(),
                             "quota" => quota = value.into(),
-                            "timezone" => timezone = value.into(),
+                            "timezone" if !value.is_empty() => timezone = value.into(),
+                            "picture" if !value.is_empty() => match Picture::from_file(value) {
+                                Ok(value) => picture = value.to_data_uri().into(),
+                                Err(err) => eprintln!(
+                                    "Warning: ignoring picture for record {}: {}",
+                                    pos + 1,
+                                    err
+                                ),
+                            },
                             _ => (),
                         }
                     }
                 }
 
-                if email.is_none() || secret.is_none() || name.is_none() {
-                    eprintln!(
-                        "Warnining: skipping record {} due to missing fields.",
-                        pos + 1
-                    );
-                    continue;
+                let (email, name, secret) =
+                    if let (Some(email), Some(name), Some(secret)) = (email, name, secret) {
+                        (email, name, secret)
+                    } else {
+                        eprintln!(
+                            "Warnining: skipping record {} due to missing fields.",
+                            pos + 1
+                        );
+                        continue;
+                    };
+                let email = match normalize_address(email) {
+                    Ok(email) => email,
+                    Err(err) => {
+                        eprintln!("Warning: skipping record {} as {}.", pos + 1, err);
+                        continue;
+                    }
+                };
+                let email = email.as_str();
+
+                // Apply template and domain defaults for any field not provided
+                let domain = email
+                    .rsplit_once('@')
+                    .map(|(_, domain)| domain.to_ascii_lowercase())
+                    .unwrap_or_default();
+                let defaults = config.account_defaults(template.as_deref(), &domain);
+                let description = description.map(String::from).or(defaults.description);
+                let quota = quota.map(parse_quota).transpose();
+                let quota = match quota
+                    .and_then(|quota| quota.or(defaults.quota).map_or(Ok(None), principal_quota))
+                {
+                    Ok(quota) => quota,
+                    Err(err) => {
+                        eprintln!("Warning: skipping record {} due to {}.", pos + 1, err);
+                        continue;
+                    }
+                };
+                let timezone = match timezone.map(parse_timezone) {
+                    Some(Ok(timezone)) => Some(timezone),
+                    Some(Err(err)) => {
+                        eprintln!("Warning: skipping record {} due to {}.", pos + 1, err);
+                        continue;
+                    }
+                    None => defaults.timezone.or_else(|| {
+                        domain_timezones
+                            .entry(domain)
+                            .or_insert_with_key(|domain| domain_timezone(&client, domain))
+                            .clone()
+                    }),
+                };
+
+                let email_aliases = available_aliases(config.alias_domain_addresses(email), &taken);
+                taken.insert(email.to_ascii_lowercase());
+                taken.extend(email_aliases.iter().map(|alias| alias.to_ascii_lowercase()));
+                accounts.push(email.to_string());
+                account_groups.push(defaults.groups);
+                let create_request = set_request
+                    .create()
+                    .ptype(Type::Individual)
+                    .email(email)
+                    .name(name)
+                    .secret(secret)
+                    .description(description)
+                    .timezone(timezone)
+                    .quota(quota)
+                    .picture(picture);
+                if !email_aliases.is_empty() {
+                    create_request.aliases(Some(email_aliases));
                 }
-
-                account_create_ids.push(
-                    set_request
-                        .create()
-                        .ptype(Type::Individual)
-                        .email(email.unwrap())
-                        .name(name.unwrap())
-                        .secret(secret.unwrap())
-                        .description(description)
-                        .timezone(timezone)
-                        .quota(quota.and_then(|q| q.parse::<u32>().ok()))
-                        .create_id()
-                        .unwrap(),
-                );
+                account_create_ids.push(create_request.create_id().unwrap());
             }
 
             // Send request
@@ -237,14 +309,22 @@ pub fn cmd_import(client: Client, command: ImportCommands) {
                 .unwrap_result("create accounts");
 
             let mut total_created = 0;
+            let mut memberships = Vec::new();
             for (pos, create_id) in account_create_ids.into_iter().enumerate() {
-                if let Err(err) = set_response.created(&create_id) {
-                    eprintln!(
-                        "Warning: Failed to create account '{}': {}",
-                        accounts[pos], err
-                    );
-                } else {
-                    total_created += 1;
+                match set_response.created(&create_id) {
+                    Ok(mut account) => {
+                        let account_id = account.take_id();
+                        for group in &account_groups[pos] {
+                            memberships.push((account_id.clone(), group));
+                        }
+                        total_created += 1;
+                    }
+                    Err(err) => {
+                        eprintln!(
+                            "Warning: Failed to create account '{}': {}",
+                            accounts[pos], err
+                        );
+                    }
                 }
             }
 
@@ -265,6 +345,16 @@ pub fn cmd_import(client: Client, command: ImportCommands) {
                 }
             }
 
+            // Add accounts to their default groups
+            if !memberships.is_empty() {
+                add_to_groups(
+                    &client,
+                    memberships
+                        .iter()
+                        .map(|(account_id, group)| (account_id.as_str(), group.as_str())),
+                );
+            }
+
             eprintln!(
                 "\nSuccessfully imported {} accounts.",
                 style(total_created).bold()
@@ -31,7 +31,9 @@ use crate::modules::{common::email_to_id, UnwrapResult};
 
 use super::{
     cli::ListCommands,
-    common::{display_principal, list_principals},
+    common::{
+        bulk_update, delete_group, display_members_principal, list_principals, update_members,
+    },
 };
 
 pub fn cmd_list(client: Client, command: ListCommands) {
@@ -78,38 +80,16 @@ pub fn cmd_list(client: Client, command: ListCommands) {
                 .unwrap_result("update list");
             eprintln!("List '{}' successfully updated.", email);
         }
-        ListCommands::AddMembers { email, members } => {
-            let update_id = email_to_id(&client, Type::List, &email);
-            let mut request = client.build();
-            let update_request = request.set_principal().update(&update_id);
-
-            for member in members {
-                update_request.member(&email_to_id(&client, Type::Individual, &member), true);
-            }
-
-            request
-                .send_set_principal()
-                .unwrap_result("update list")
-                .updated(&update_id)
-                .unwrap_result("update list");
-
+        ListCommands::AddMembers {
+            email,
+            members,
+            external,
+        } => {
+            update_members(&client, Type::List, &email, &members, true, external);
             eprintln!("List '{}' successfully updated.", email);
         }
         ListCommands::RemoveMembers { email, members } => {
-            let update_id = email_to_id(&client, Type::List, &email);
-            let mut request = client.build();
-            let update_request = request.set_principal().update(&update_id);
-
-            for member in members {
-                update_request.member(&email_to_id(&client, Type::Individual, &member), false);
-            }
-
-            request
-                .send_set_principal()
-                .unwrap_result("update list")
-                .updated(&update_id)
-                .unwrap_result("update list");
-
+            update_members(&client, Type::List, &email, &members, false, false);
             eprintln!("List '{}' successfully updated.", email);
         }
         ListCommands::List { filter } => {
@@ -120,17 +100,26 @@ pub fn cmd_list(client: Client, command: ListCommands) {
                 &[Property::Email, Property::Name, Property::Description],
             );
         }
-        ListCommands::Display { email } => {
-            display_principal(
-                &client,
-                &email_to_id(&client, Type::List, &email),
-                &[
-                    Property::Email,
-                    Property::Name,
-                    Property::Description,
-                    Property::Members,
-                ],
-            );
+        ListCommands::Delete {
+            email,
+            force,
+            show_members,
+        } => {
+            delete_group(&client, Type::List, &email, force, show_members);
+        }
+        ListCommands::Display { email, expand } => {
+            display_members_principal(&client, Type::List, &email, expand);
+        }
+        ListCommands::BulkUpdate {
+            filter,
+            force,
+            description,
+        } => {
+            bulk_update(&client, Type::List, filter, force, |update_request| {
+                if description.is_some() {
+                    update_request.description(description.clone());
+                }
+            });
         }
     }
 }
@@ -21,17 +21,34 @@
  * for more details.
 */
 
-use std::{collections::HashMap, fmt::Display, io::Read};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{BufRead, Read, Write},
+};
 
 use jmap_client::principal::Property;
 
 pub mod account;
+pub mod address;
+pub mod alias_domain;
 pub mod cli;
 pub mod common;
+pub mod config;
+pub mod dkim;
+pub mod dns;
 pub mod domain;
+pub mod export;
 pub mod group;
 pub mod import;
 pub mod list;
+pub mod picture;
+pub mod principal;
+pub mod report;
+pub mod template;
+pub mod timezone;
+pub mod usage;
+pub mod whois;
 
 pub trait UnwrapResult<T> {
     fn unwrap_result(self, action: &str) -> T;
@@ -107,6 +124,17 @@ pub fn read_file(path: &str) -> Vec<u8> {
     }
 }
 
+pub fn confirm(message: &str) -> bool {
+    print!("\n{} [y/N] ", message);
+    std::io::stdout().flush().unwrap();
+    let mut answer = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .unwrap_result("read answer");
+    matches!(answer.trim(), "y" | "Y" | "yes" | "Yes" | "YES")
+}
+
 pub fn get(url: &str) -> HashMap<String, serde_json::Value> {
     serde_json::from_slice(
         &reqwest::blocking::Client::builder()
@@ -0,0 +1,310 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Command Line Interface.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::path::Path;
+
+const MAX_PICTURE_SIZE: usize = 1024 * 1024;
+const MAX_PICTURE_DIMENSION: u32 = 2048;
+
+pub struct Picture {
+    pub mime_type: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub contents: Vec<u8>,
+}
+
+impl Picture {
+    pub fn parse(contents: Vec<u8>) -> Result<Self, String> {
+        if contents.len() > MAX_PICTURE_SIZE {
+            return Err(format!(
+                "picture is {} bytes, the maximum allowed size is {} bytes",
+                contents.len(),
+                MAX_PICTURE_SIZE
+            ));
+        }
+
+        let (mime_type, dimensions) = if contents.starts_with(b"\x89PNG\r\n\x1a\n") {
+            ("image/png", png_dimensions(&contents))
+        } else if contents.starts_with(b"\xff\xd8") {
+            ("image/jpeg", jpeg_dimensions(&contents))
+        } else if contents.starts_with(b"GIF87a") || contents.starts_with(b"GIF89a") {
+            ("image/gif", gif_dimensions(&contents))
+        } else if contents.starts_with(b"RIFF") && contents.get(8..12) == Some(b"WEBP") {
+            ("image/webp", webp_dimensions(&contents))
+        } else {
+            return Err("unsupported picture format, expected PNG, JPEG, GIF or WebP".to_string());
+        };
+        let (width, height) =
+            dimensions.ok_or_else(|| "picture is corrupted or truncated".to_string())?;
+
+        if width == 0
+            || height == 0
+            || width > MAX_PICTURE_DIMENSION
+            || height > MAX_PICTURE_DIMENSION
+        {
+            return Err(format!(
+                "picture is {}x{} pixels, the maximum allowed dimensions are {}x{} pixels",
+                width, height, MAX_PICTURE_DIMENSION, MAX_PICTURE_DIMENSION
+            ));
+        }
+
+        Ok(Picture {
+            mime_type,
+            width,
+            height,
+            contents,
+        })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        Picture::parse(
+            std::fs::read(path)
+                .map_err(|err| format!("failed to read '{}': {}", path.display(), err))?,
+        )
+    }
+
+    pub fn from_data_uri(uri: &str) -> Option<Vec<u8>> {
+        let (header, data) = uri.strip_prefix("data:")?.split_once(',')?;
+        if header.ends_with(";base64") {
+            base64::decode(data.trim()).ok()
+        } else {
+            Some(data.as_bytes().to_vec())
+        }
+    }
+
+    pub fn to_data_uri(&self) -> String {
+        format!(
+            "data:{};base64,{}",
+            self.mime_type,
+            base64::encode(&self.contents)
+        )
+    }
+}
+
+fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    Some((be_u32(data.get(16..20)?), be_u32(data.get(20..24)?)))
+}
+
+fn gif_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    Some((le_u16(data.get(6..8)?), le_u16(data.get(8..10)?)))
+}
+
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut pos = 2;
+    loop {
+        // Skip fill bytes
+        while *data.get(pos)? == 0xff && *data.get(pos + 1)? == 0xff {
+            pos += 1;
+        }
+        if *data.get(pos)? != 0xff {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        match marker {
+            0xc0..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) => {
+                return Some((
+                    be_u16(data.get(pos + 7..pos + 9)?),
+                    be_u16(data.get(pos + 5..pos + 7)?),
+                ));
+            }
+            0x01 | 0xd0..=0xd8 => {
+                pos += 2;
+            }
+            0xd9 | 0xda => return None,
+            _ => {
+                pos += 2 + be_u16(data.get(pos + 2..pos + 4)?) as usize;
+            }
+        }
+    }
+}
+
+fn webp_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    match data.get(12..16)? {
+        b"VP8 " => Some((
+            le_u16(data.get(26..28)?) & 0x3fff,
+            le_u16(data.get(28..30)?) & 0x3fff,
+        )),
+        b"VP8L" => {
+            let b = data.get(21..25)?;
+            Some((
+                1 + (((b[1] as u32 & 0x3f) << 8) | b[0] as u32),
+                1 + (((b[3] as u32 & 0x0f) << 10)
+                    | ((b[2] as u32) << 2)
+                    | ((b[1] as u32 & 0xc0) >> 6)),
+            ))
+        }
+        b"VP8X" => {
+            let b = data.get(24..30)?;
+            Some((
+                1 + (b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16),
+                1 + (b[3] as u32 | (b[4] as u32) << 8 | (b[5] as u32) << 16),
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn be_u16(bytes: &[u8]) -> u32 {
+    u16::from_be_bytes([bytes[0], bytes[1]]) as u32
+}
+
+fn le_u16(bytes: &[u8]) -> u32 {
+    u16::from_le_bytes([bytes[0], bytes[1]]) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Picture;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[8, 6, 0, 0, 0]);
+        data
+    }
+
+    fn webp(chunk: &[u8], header: &[u8]) -> Vec<u8> {
+        let mut data = b"RIFF\x00\x00\x00\x00WEBP".to_vec();
+        data.extend_from_slice(chunk);
+        data.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        data.extend_from_slice(header);
+        data
+    }
+
+    fn assert_picture(data: Vec<u8>, mime_type: &str, width: u32, height: u32) {
+        let picture = Picture::parse(data).unwrap();
+        assert_eq!(
+            (picture.mime_type, picture.width, picture.height),
+            (mime_type, width, height)
+        );
+    }
+
+    #[test]
+    fn parse_png() {
+        assert_picture(png(640, 480), "image/png", 640, 480);
+    }
+
+    #[test]
+    fn parse_gif() {
+        assert_picture(
+            b"GIF89a\x40\x01\xc8\x00\x00\x00\x00".to_vec(),
+            "image/gif",
+            320,
+            200,
+        );
+        assert_picture(b"GIF87a\x01\x00\x01\x00".to_vec(), "image/gif", 1, 1);
+    }
+
+    #[test]
+    fn parse_jpeg() {
+        let mut data =
+            b"\xff\xd8\xff\xe0\x00\x10JFIF\x00\x01\x01\x00\x00\x01\x00\x01\x00\x00".to_vec();
+        // Huffman table and fill bytes before the start of frame
+        data.extend_from_slice(b"\xff\xc4\x00\x04\x00\x00\xff\xff");
+        data.extend_from_slice(b"\xff\xc2\x00\x11\x08\x01\xe0\x02\x80\x03");
+        assert_picture(data, "image/jpeg", 640, 480);
+
+        let data = b"\xff\xd8\xff\xda\x00\x08\x01\x01\x00\x00\x3f\x00".to_vec();
+        assert!(Picture::parse(data).is_err());
+    }
+
+    #[test]
+    fn parse_webp() {
+        let mut header = vec![0x10, 0x02, 0x00, 0x9d, 0x01, 0x2a];
+        header.extend_from_slice(&[0x80, 0x02, 0xe0, 0x01]);
+        assert_picture(webp(b"VP8 ", &header), "image/webp", 640, 480);
+        assert_picture(
+            webp(b"VP8L", &[0x2f, 0x2b, 0xc1, 0x31, 0x00]),
+            "image/webp",
+            300,
+            200,
+        );
+        assert_picture(
+            webp(
+                b"VP8X",
+                &[0x10, 0x00, 0x00, 0x00, 0xff, 0x07, 0x00, 0x0f, 0x00, 0x00],
+            ),
+            "image/webp",
+            2048,
+            16,
+        );
+    }
+
+    #[test]
+    fn reject_invalid_pictures() {
+        for (data, error) in [
+            (
+                b"BM\x00\x00".to_vec(),
+                "unsupported picture format, expected PNG, JPEG, GIF or WebP",
+            ),
+            (
+                b"\x89PNG\r\n\x1a\n".to_vec(),
+                "picture is corrupted or truncated",
+            ),
+            (
+                b"\xff\xd8\xff".to_vec(),
+                "picture is corrupted or truncated",
+            ),
+            (
+                webp(b"VP8X", &[0x10, 0x00]),
+                "picture is corrupted or truncated",
+            ),
+            (
+                png(0, 480),
+                "picture is 0x480 pixels, the maximum allowed dimensions are 2048x2048 pixels",
+            ),
+            (
+                png(4096, 16),
+                "picture is 4096x16 pixels, the maximum allowed dimensions are 2048x2048 pixels",
+            ),
+            (
+                vec![0; 1024 * 1024 + 1],
+                "picture is 1048577 bytes, the maximum allowed size is 1048576 bytes",
+            ),
+        ] {
+            assert_eq!(Picture::parse(data).err().as_deref(), Some(error));
+        }
+    }
+
+    #[test]
+    fn data_uris() {
+        let picture = Picture::parse(png(1, 1)).unwrap();
+        let uri = picture.to_data_uri();
+        assert!(uri.starts_with("data:image/png;base64,"));
+        assert_eq!(Picture::from_data_uri(&uri), Some(png(1, 1)));
+        assert_eq!(
+            Picture::from_data_uri("data:text/plain,abc"),
+            Some(b"abc".to_vec())
+        );
+        assert_eq!(Picture::from_data_uri("https://example.com/a.png"), None);
+    }
+}
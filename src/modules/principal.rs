@@ -0,0 +1,166 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Command Line Interface.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::collections::HashMap;
+
+use jmap_client::{
+    client::Client,
+    principal::{Property, ACL},
+};
+use prettytable::{Attr, Cell, Row, Table};
+
+use crate::modules::UnwrapResult;
+
+use super::{
+    cli::{AclCommands, CapabilityCommands, PrincipalCommands},
+    common::{acl_name, display_principal, parse_acl, principal_names, principal_to_id},
+};
+
+pub fn cmd_principal(client: Client, command: PrincipalCommands) {
+    match command {
+        PrincipalCommands::Acl(AclCommands::Show { principal }) => {
+            let acl = fetch_acl(&client, &principal_to_id(&client, &principal));
+            if !acl.is_empty() {
+                let names = principal_names(&client, acl.keys());
+                let mut table = Table::new();
+                table.add_row(Row::new(vec![
+                    Cell::new("Grantee").with_style(Attr::Bold),
+                    Cell::new("Rights").with_style(Attr::Bold),
+                ]));
+                for (id, rights) in &acl {
+                    table.add_row(Row::new(vec![
+                        Cell::new(names.get(id).unwrap_or(id)),
+                        Cell::new(&rights.iter().map(acl_name).collect::<Vec<_>>().join(", ")),
+                    ]));
+                }
+                eprintln!();
+                table.printstd();
+            }
+
+            eprintln!(
+                "\n\n{} record{} found.\n",
+                acl.len(),
+                if acl.len() == 1 { "" } else { "s" }
+            );
+        }
+        PrincipalCommands::Acl(AclCommands::Grant {
+            principal,
+            grantee,
+            rights,
+        }) => {
+            let update_id = principal_to_id(&client, &principal);
+            let grantee_id = principal_to_id(&client, &grantee);
+            let mut acl = fetch_acl(&client, &update_id);
+            let grantee_rights = acl.entry(grantee_id).or_insert_with(Vec::new);
+            for right in rights.iter().map(|r| parse_acl(r)) {
+                if !grantee_rights.contains(&right) {
+                    grantee_rights.push(right);
+                }
+            }
+
+            let mut request = client.build();
+            request
+                .set_principal()
+                .update(&update_id)
+                .acl(Some(acl.into_iter().collect()));
+            request
+                .send_set_principal()
+                .unwrap_result("update principal")
+                .updated(&update_id)
+                .unwrap_result("update principal");
+            eprintln!("Principal '{}' successfully updated.", principal);
+        }
+        PrincipalCommands::Acl(AclCommands::Revoke {
+            principal,
+            grantee,
+            rights,
+        }) => {
+            let update_id = principal_to_id(&client, &principal);
+            let grantee_id = principal_to_id(&client, &grantee);
+            let mut acl = fetch_acl(&client, &update_id);
+            if rights.is_empty() {
+                acl.remove(&grantee_id);
+            } else if let Some(grantee_rights) = acl.get_mut(&grantee_id) {
+                let rights = rights.iter().map(|r| parse_acl(r)).collect::<Vec<_>>();
+                grantee_rights.retain(|right| !rights.contains(right));
+                if grantee_rights.is_empty() {
+                    acl.remove(&grantee_id);
+                }
+            }
+
+            let mut request = client.build();
+            request
+                .set_principal()
+                .update(&update_id)
+                .acl(Some(acl.into_iter().collect()));
+            request
+                .send_set_principal()
+                .unwrap_result("update principal")
+                .updated(&update_id)
+                .unwrap_result("update principal");
+            eprintln!("Principal '{}' successfully updated.", principal);
+        }
+        PrincipalCommands::Capabilities(CapabilityCommands::Show { principal }) => {
+            display_principal(
+                &client,
+                &principal_to_id(&client, &principal),
+                &[Property::Capabilities],
+            );
+        }
+        PrincipalCommands::Capabilities(CapabilityCommands::Set {
+            principal,
+            capabilities,
+        }) => {
+            let update_id = principal_to_id(&client, &principal);
+            let mut request = client.build();
+            request
+                .set_principal()
+                .update(&update_id)
+                .capabilities(if !capabilities.is_empty() {
+                    Some(capabilities)
+                } else {
+                    None
+                });
+            request
+                .send_set_principal()
+                .unwrap_result("update principal")
+                .updated(&update_id)
+                .unwrap_result("update principal");
+            eprintln!("Principal '{}' successfully updated.", principal);
+        }
+    }
+}
+
+fn fetch_acl(client: &Client, id: &str) -> HashMap<String, Vec<ACL>> {
+    client
+        .principal_get(id, [Property::ACL].into())
+        .unwrap_result("fetch principal")
+        .unwrap_result("find principal")
+        .acl()
+        .map(|acl| {
+            acl.iter()
+                .map(|(id, rights)| (id.clone(), rights.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
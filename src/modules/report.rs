@@ -0,0 +1,147 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Command Line Interface.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use chrono::Utc;
+use jmap_client::{
+    client::Client,
+    principal::{query, Property, Type},
+};
+use prettytable::{Attr, Cell, Row, Table};
+
+use super::{
+    address::display_domain,
+    cli::{OutputFormat, ReportCommands},
+    common::fetch_principals,
+    config::Config,
+    dkim::{selector_date, DkimKey},
+    usage::{principals_usage, print_usage, USAGE_PROPERTIES},
+};
+
+pub fn cmd_report(client: Client, command: ReportCommands) {
+    match command {
+        ReportCommands::Quota { over, format } => {
+            let threshold = over.unwrap_or(90.0);
+            let principals = fetch_principals(
+                &client,
+                query::Filter::ptype(Type::Individual).into(),
+                &USAGE_PROPERTIES,
+            )
+            .into_iter()
+            .filter(|principal| principal.quota().map_or(false, |quota| quota > 0))
+            .collect::<Vec<_>>();
+
+            let mut results = principals_usage(&client, &principals)
+                .into_iter()
+                .filter(|usage| usage.percentage().map_or(false, |p| p >= threshold))
+                .collect::<Vec<_>>();
+            results.sort_by(|a, b| {
+                b.percentage()
+                    .partial_cmp(&a.percentage())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+
+            print_usage(&results, format.unwrap_or(OutputFormat::Table));
+        }
+        ReportCommands::Dkim { max_age } => {
+            let max_age = max_age.unwrap_or(180) as i64 * 86400;
+            let now = Utc::now().timestamp();
+            let config = Config::load();
+            let mut table = Table::new();
+            table.add_row(Row::new(
+                ["Domain", "Selector", "Key", "Age", "Rotation", "Status"]
+                    .iter()
+                    .map(|h| Cell::new(h).with_style(Attr::Bold))
+                    .collect(),
+            ));
+
+            let mut results = 0;
+            for domain in fetch_principals(
+                &client,
+                query::Filter::ptype(Type::Domain).into(),
+                &[Property::Name, Property::DKIM, Property::Secret],
+            ) {
+                let name = domain.name().unwrap_or("").to_ascii_lowercase();
+                let selector = if let Some(selector) = domain.dkim().and_then(|d| d.selector()) {
+                    selector
+                } else {
+                    continue;
+                };
+                let rotation = config.dkim.get(&name);
+
+                // Keys are rotated once they are older than the domain's
+                // DKIM expiration, or than the maximum age if none is set.
+                let interval = domain
+                    .dkim()
+                    .and_then(|d| d.expiration())
+                    .filter(|expiration| *expiration > 0)
+                    .unwrap_or(max_age);
+                let age = rotation
+                    .and_then(|rotation| rotation.activated_at)
+                    .or_else(|| selector_date(selector))
+                    .map(|activated_at| (now - activated_at).max(0));
+                let status = if let Some(pending) =
+                    rotation.and_then(|rotation| rotation.pending.as_ref())
+                {
+                    format!("'{}' pending activation", pending.selector)
+                } else if let Some(age) = age {
+                    if age >= interval {
+                        "due".to_string()
+                    } else {
+                        continue;
+                    }
+                } else {
+                    "age unknown".to_string()
+                };
+
+                table.add_row(Row::new(vec![
+                    Cell::new(&display_domain(&name)),
+                    Cell::new(selector),
+                    Cell::new(
+                        &domain
+                            .secret()
+                            .and_then(|secret| DkimKey::from_pem(secret).ok())
+                            .map(|dkim_key| dkim_key.description())
+                            .unwrap_or_default(),
+                    ),
+                    Cell::new(
+                        &age.map(|age| format!("{} days", age / 86400))
+                            .unwrap_or_default(),
+                    ),
+                    Cell::new(&format!("{} days", interval / 86400)),
+                    Cell::new(&status),
+                ]));
+                results += 1;
+            }
+
+            if results > 0 {
+                eprintln!();
+                table.printstd();
+            }
+            eprintln!(
+                "\n\n{} record{} found.\n",
+                results,
+                if results == 1 { "" } else { "s" }
+            );
+        }
+    }
+}
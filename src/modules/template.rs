@@ -0,0 +1,129 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Command Line Interface.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use prettytable::{Attr, Cell, Row, Table};
+
+use super::{
+    cli::{AccountDefaultsArgs, TemplateCommands},
+    common::principal_quota,
+    config::{AccountDefaults, Config},
+    timezone::validate_timezone,
+    usage::format_size,
+    UnwrapResult,
+};
+
+pub fn cmd_template(command: TemplateCommands) {
+    let mut config = Config::load();
+
+    match command {
+        TemplateCommands::Set { name, defaults } => {
+            let template = config.templates.entry(name.clone()).or_default();
+            update_defaults(template, defaults);
+            config.save();
+            eprintln!("Template '{}' successfully saved.", name);
+        }
+        TemplateCommands::Delete { name } => {
+            if config.templates.remove(&name).is_some() {
+                config.save();
+                eprintln!("Template '{}' successfully deleted.", name);
+            } else {
+                eprintln!("Error: Template '{}' does not exist.", name);
+                std::process::exit(1);
+            }
+        }
+        TemplateCommands::Display { name } => {
+            if let Some(template) = config.templates.get(&name) {
+                display_defaults(template);
+            } else {
+                eprintln!("Error: Template '{}' does not exist.", name);
+                std::process::exit(1);
+            }
+        }
+        TemplateCommands::List => {
+            if !config.templates.is_empty() {
+                let mut table = Table::new();
+                table.add_row(Row::new(
+                    ["Name", "Description", "Quota", "Timezone", "Groups"]
+                        .iter()
+                        .map(|h| Cell::new(h).with_style(Attr::Bold))
+                        .collect(),
+                ));
+                for (name, template) in &config.templates {
+                    let mut row = vec![Cell::new(name)];
+                    row.extend(defaults_cells(template));
+                    table.add_row(Row::new(row));
+                }
+                eprintln!();
+                table.printstd();
+            }
+
+            eprintln!(
+                "\n\n{} record{} found.\n",
+                config.templates.len(),
+                if config.templates.len() == 1 { "" } else { "s" }
+            );
+        }
+    }
+}
+
+pub fn update_defaults(defaults: &mut AccountDefaults, args: AccountDefaultsArgs) {
+    if args.description.is_some() {
+        defaults.description = args.description;
+    }
+    if let Some(quota) = args.quota {
+        // Reject quotas the server cannot store before they reach the config
+        principal_quota(quota).unwrap_result("set quota");
+        defaults.quota = Some(quota);
+    }
+    if args.timezone.is_some() {
+        defaults.timezone = validate_timezone(args.timezone);
+    }
+    if !args.groups.is_empty() {
+        defaults.groups = args.groups;
+    }
+}
+
+pub fn display_defaults(defaults: &AccountDefaults) {
+    eprintln!();
+    let mut table = Table::new();
+    for (name, value) in ["Description", "Quota", "Timezone", "Groups"]
+        .iter()
+        .zip(defaults_cells(defaults))
+    {
+        table.add_row(Row::new(vec![
+            Cell::new(name).with_style(Attr::Bold),
+            value,
+        ]));
+    }
+    table.printstd();
+    eprintln!();
+}
+
+fn defaults_cells(defaults: &AccountDefaults) -> Vec<Cell> {
+    vec![
+        Cell::new(defaults.description.as_deref().unwrap_or("")),
+        Cell::new(&defaults.quota.map(format_size).unwrap_or_default()),
+        Cell::new(defaults.timezone.as_deref().unwrap_or("")),
+        Cell::new(&defaults.groups.join(", ")),
+    ]
+}
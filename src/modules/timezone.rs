@@ -0,0 +1,108 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Command Line Interface.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use chrono_tz::TZ_VARIANTS;
+
+use super::{cli::TimezoneCommands, common::edit_distance};
+
+pub fn cmd_timezones(command: TimezoneCommands) {
+    match command {
+        TimezoneCommands::List { filter } => {
+            let filter = filter.map(|filter| filter.to_lowercase());
+            let mut total = 0;
+            for name in TZ_VARIANTS.iter().map(|tz| tz.name()) {
+                if filter
+                    .as_ref()
+                    .map_or(true, |filter| name.to_lowercase().contains(filter))
+                {
+                    println!("{}", name);
+                    total += 1;
+                }
+            }
+
+            eprintln!(
+                "\n{} timezone{} found.\n",
+                total,
+                if total == 1 { "" } else { "s" }
+            );
+        }
+    }
+}
+
+pub fn parse_timezone(timezone: &str) -> Result<String, String> {
+    let timezone = timezone.trim();
+    if let Some(tz) = TZ_VARIANTS
+        .iter()
+        .find(|tz| tz.name().eq_ignore_ascii_case(timezone))
+    {
+        return Ok(tz.name().to_string());
+    }
+
+    // Suggest timezones with a similar name
+    let timezone_lower = timezone.to_lowercase();
+    let mut suggestions = TZ_VARIANTS
+        .iter()
+        .map(|tz| tz.name())
+        .filter_map(|name| {
+            let name_lower = name.to_lowercase();
+            let distance = edit_distance(&name_lower, &timezone_lower);
+            if distance <= 3
+                || name_lower
+                    .rsplit_once('/')
+                    .map_or(false, |(_, city)| city == timezone_lower)
+            {
+                Some((distance, name))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    suggestions.sort_unstable();
+
+    if !suggestions.is_empty() {
+        Err(format!(
+            "unknown timezone '{}', did you mean {}?",
+            timezone,
+            suggestions
+                .iter()
+                .take(5)
+                .map(|(_, name)| format!("'{}'", name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    } else {
+        Err(format!(
+            "unknown timezone '{}', run 'timezones list' to see all valid timezones",
+            timezone
+        ))
+    }
+}
+
+pub fn validate_timezone(timezone: Option<String>) -> Option<String> {
+    timezone.map(|timezone| {
+        parse_timezone(&timezone).unwrap_or_else(|err| {
+            eprintln!("Error: {}.", err);
+            std::process::exit(1);
+        })
+    })
+}
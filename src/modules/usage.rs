@@ -0,0 +1,229 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Command Line Interface.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_client::{
+    client::Client,
+    email,
+    principal::{Principal, Property},
+};
+use prettytable::{Attr, Cell, Row, Table};
+use serde_json::json;
+
+use super::{cli::OutputFormat, UnwrapResult};
+
+pub struct AccountUsage {
+    pub email: String,
+    pub used: u64,
+    pub quota: Option<u64>,
+}
+
+impl AccountUsage {
+    pub fn percentage(&self) -> Option<f64> {
+        self.quota
+            .filter(|quota| *quota > 0)
+            .map(|quota| self.used as f64 * 100.0 / quota as f64)
+    }
+}
+
+pub const USAGE_PROPERTIES: [Property; 2] = [Property::Email, Property::Quota];
+
+pub fn principals_usage(client: &Client, principals: &[Principal]) -> Vec<AccountUsage> {
+    principals
+        .iter()
+        .map(|principal| AccountUsage {
+            email: principal.email().unwrap_or("").to_string(),
+            used: account_usage(client, principal.id().unwrap_result("obtain principal id")),
+            quota: principal.quota().map(u64::from),
+        })
+        .collect()
+}
+
+pub fn account_usage(client: &Client, account_id: &str) -> u64 {
+    let page_size = client
+        .session()
+        .core_capabilities()
+        .map(|c| c.max_objects_in_get())
+        .unwrap_or(500);
+    let mut position = 0;
+    let mut used = 0;
+
+    loop {
+        let mut request = client.build();
+        let query_ref = request
+            .query_email()
+            .account_id(account_id)
+            .position(position)
+            .limit(page_size)
+            .result_reference();
+        request
+            .get_email()
+            .account_id(account_id)
+            .ids_ref(query_ref)
+            .properties([email::Property::Size]);
+        let emails = request
+            .send()
+            .unwrap_result("fetch emails")
+            .unwrap_method_responses()
+            .pop()
+            .unwrap_result("process response: received an empty response from server")
+            .unwrap_get_email()
+            .unwrap_result("fetch emails")
+            .take_list();
+
+        used += emails.iter().map(|e| e.size() as u64).sum::<u64>();
+        if emails.len() < page_size {
+            break;
+        }
+        position += emails.len() as i32;
+    }
+
+    used
+}
+
+pub fn print_usage(results: &[AccountUsage], format: OutputFormat) {
+    match format {
+        OutputFormat::Table => {
+            if !results.is_empty() {
+                let mut table = Table::new();
+                table.add_row(Row::new(
+                    ["E-mail", "Used", "Quota", "Usage"]
+                        .iter()
+                        .map(|h| Cell::new(h).with_style(Attr::Bold))
+                        .collect(),
+                ));
+                for result in results {
+                    table.add_row(Row::new(vec![
+                        Cell::new(&result.email),
+                        Cell::new(&format_size(result.used)),
+                        Cell::new(
+                            &result
+                                .quota
+                                .map(format_size)
+                                .unwrap_or_else(|| "unlimited".to_string()),
+                        ),
+                        Cell::new(
+                            &result
+                                .percentage()
+                                .map(|p| format!("{:.1}%", p))
+                                .unwrap_or_default(),
+                        ),
+                    ]));
+                }
+                eprintln!();
+                table.printstd();
+            }
+
+            eprintln!(
+                "\n\n{} record{} found.\n",
+                results.len(),
+                if results.len() == 1 { "" } else { "s" }
+            );
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            writer
+                .write_record(["email", "used", "quota", "percentage"])
+                .unwrap_result("write CSV");
+            for result in results {
+                writer
+                    .write_record([
+                        result.email.clone(),
+                        result.used.to_string(),
+                        result.quota.map(|q| q.to_string()).unwrap_or_default(),
+                        result
+                            .percentage()
+                            .map(|p| format!("{:.2}", p))
+                            .unwrap_or_default(),
+                    ])
+                    .unwrap_result("write CSV");
+            }
+            writer.flush().unwrap_result("write CSV");
+        }
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(
+                    &results
+                        .iter()
+                        .map(|result| {
+                            json!({
+                                "email": result.email,
+                                "used": result.used,
+                                "quota": result.quota,
+                                "percentage": result.percentage(),
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                )
+                .unwrap_result("serialize JSON")
+            );
+        }
+    }
+}
+
+pub fn parse_percentage(value: &str) -> Result<f64, String> {
+    value
+        .trim()
+        .trim_end_matches('%')
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|p| (0.0..=100.0).contains(p))
+        .ok_or_else(|| format!("invalid percentage '{}'", value))
+}
+
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_percentage;
+
+    #[test]
+    fn parse_percentages() {
+        for (value, expected) in [
+            ("0", 0.0),
+            ("90", 90.0),
+            ("85%", 85.0),
+            (" 99.5 % ", 99.5),
+            ("100%", 100.0),
+        ] {
+            assert_eq!(parse_percentage(value), Ok(expected), "{}", value);
+        }
+        for value in ["", "%", "-1", "100.1", "101%", "ninety"] {
+            assert!(parse_percentage(value).is_err(), "{}", value);
+        }
+    }
+}
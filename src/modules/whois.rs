@@ -0,0 +1,165 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Command Line Interface.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::collections::HashMap;
+
+use jmap_client::{
+    client::Client,
+    principal::{Principal, Type},
+};
+use prettytable::{Attr, Cell, Row, Table};
+
+use super::{
+    address::{address_domain, normalize_address},
+    common::{expand_members, fetch_memberships, has_alias, type_name},
+};
+
+pub fn cmd_whois(client: Client, address: String) {
+    let address = normalize_address(&address).unwrap_or_else(|_| address.to_lowercase());
+    let principals = fetch_memberships(&client);
+    let principals_by_id = principals
+        .iter()
+        .filter_map(|principal| Some((principal.id()?, principal)))
+        .collect::<HashMap<_, _>>();
+    let owners = address_owners(&principals, &address);
+
+    if !owners.is_empty() {
+        let mut table = Table::new();
+        table.add_row(Row::new(
+            ["Owner", "Type", "Match", "Recipients"]
+                .iter()
+                .map(|h| Cell::new(h).with_style(Attr::Bold))
+                .collect(),
+        ));
+        for (principal, match_type) in &owners {
+            let recipients = if matches!(principal.ptype(), Some(Type::Group | Type::List)) {
+                expand_members(&principals_by_id, principal.id().unwrap_or("")).join("\n")
+            } else {
+                String::new()
+            };
+            table.add_row(Row::new(vec![
+                Cell::new(principal.email().unwrap_or("")),
+                Cell::new(principal.ptype().map(type_name).unwrap_or("")),
+                Cell::new(match_type),
+                Cell::new(&recipients),
+            ]));
+        }
+        eprintln!();
+        table.printstd();
+        eprintln!();
+    } else {
+        eprintln!("No principal receives messages for '{}'.", address);
+        std::process::exit(1);
+    }
+}
+
+/// Returns the principals receiving messages for an address and how they
+/// match it. Exact matches take precedence over the catch-all address.
+fn address_owners<'x>(
+    principals: &'x [Principal],
+    address: &str,
+) -> Vec<(&'x Principal, &'static str)> {
+    let owners = principals
+        .iter()
+        .filter_map(|principal| {
+            if principal
+                .email()
+                .map_or(false, |email| email.eq_ignore_ascii_case(address))
+            {
+                Some((principal, "primary"))
+            } else if has_alias(principal, address) {
+                Some((principal, "alias"))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    match address_domain(address) {
+        Some(domain) if owners.is_empty() => {
+            let catch_all = format!("@{}", domain);
+            principals
+                .iter()
+                .filter(|principal| has_alias(principal, &catch_all))
+                .map(|principal| (principal, "catch-all"))
+                .collect()
+        }
+        _ => owners,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jmap_client::principal::Principal;
+
+    use super::address_owners;
+
+    fn principal(email: &str, aliases: &[&str]) -> Principal {
+        serde_json::from_value(serde_json::json!({
+            "id": email,
+            "type": "individual",
+            "email": email,
+            "aliases": aliases,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn match_address_owners() {
+        let principals = [
+            principal("alice@example.com", &["sales@example.com"]),
+            principal(
+                "bob@example.com",
+                &["sales@example.com", "bobby@example.org"],
+            ),
+            principal("postmaster@example.com", &["@example.com"]),
+        ];
+        for (address, owners) in [
+            ("alice@example.com", &[("alice@example.com", "primary")][..]),
+            (
+                "sales@example.com",
+                &[("alice@example.com", "alias"), ("bob@example.com", "alias")],
+            ),
+            ("Bobby@Example.org", &[("bob@example.com", "alias")]),
+            (
+                "postmaster@example.com",
+                &[("postmaster@example.com", "primary")],
+            ),
+            (
+                "unknown@example.com",
+                &[("postmaster@example.com", "catch-all")],
+            ),
+            ("unknown@example.org", &[]),
+            ("unknown", &[]),
+        ] {
+            assert_eq!(
+                address_owners(&principals, address)
+                    .into_iter()
+                    .map(|(principal, match_type)| (principal.email().unwrap(), match_type))
+                    .collect::<Vec<_>>(),
+                owners,
+                "{}",
+                address
+            );
+        }
+    }
+}